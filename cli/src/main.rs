@@ -0,0 +1,331 @@
+//! Command-line tool to compress and decompress sparse files.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::process::ExitCode;
+use zerocrush::{CodeTrainer, Decoder, DecoderState, Encoder, EncoderState, Extent};
+
+const USAGE: &str = "usage: zerocrush (compress | decompress) <input> <output>
+       zerocrush train <name> <input>...";
+
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// Minimum length of a zero run left as a hole, as seeking past shorter runs costs more than
+/// writing them.
+const MIN_HOLE_LEN: u64 = 4096;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = match args.as_slice() {
+        [command, input, output] if command == "compress" => compress(input, output),
+        [command, input, output] if command == "decompress" => decompress(input, output),
+        [command, name, inputs @ ..] if command == "train" && !inputs.is_empty() => {
+            train(name, inputs)
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("zerocrush: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Compresses a file, feeding holes to the encoder as zero runs without reading them.
+fn compress(input: &str, output: &str) -> io::Result<()> {
+    let mut input = File::open(input)?;
+    let mut output = BufWriter::new(File::create(output)?);
+
+    let input_len = input.metadata()?.len();
+
+    let mut encoder = Encoder::new();
+    let mut chunk = vec![0u8; CHUNK_LEN];
+    let mut buffer = vec![0u8; CHUNK_LEN];
+
+    let mut offset = 0;
+
+    while offset < input_len {
+        let data_start = seek_data(&mut input, offset, input_len)?;
+        let data_end = seek_hole(&mut input, data_start, input_len)?;
+
+        let mut remaining = data_start - offset;
+
+        while remaining != 0 {
+            let (consumed_len, produced_len, _) = encoder.step_zeroes(remaining, &mut buffer);
+
+            output.write_all(&buffer[..produced_len])?;
+            remaining -= consumed_len;
+        }
+
+        input.seek(SeekFrom::Start(data_start))?;
+
+        let mut remaining = data_end - data_start;
+
+        while remaining != 0 {
+            let len = remaining.min(CHUNK_LEN as u64) as usize;
+            input.read_exact(&mut chunk[..len])?;
+            encode(&mut encoder, &chunk[..len], &mut buffer, &mut output)?;
+            remaining -= len as u64;
+        }
+
+        offset = data_end;
+    }
+
+    encoder.set_consumed_bytes_end();
+    encode(&mut encoder, &[], &mut buffer, &mut output)?;
+
+    output.flush()
+}
+
+fn encode(
+    encoder: &mut Encoder,
+    mut consumed: &[u8],
+    buffer: &mut [u8],
+    output: &mut impl Write,
+) -> io::Result<()> {
+    loop {
+        let (consumed_len, produced_len, state) = encoder.step(consumed, buffer);
+
+        consumed = &consumed[consumed_len..];
+        output.write_all(&buffer[..produced_len])?;
+
+        if state != EncoderState::CanProduce {
+            return Ok(());
+        }
+    }
+}
+
+/// Decompresses a file, leaving zero runs as holes when writing to a regular file.
+fn decompress(input: &str, output: &str) -> io::Result<()> {
+    let mut input = File::open(input)?;
+    let output = File::create(output)?;
+
+    let sparse = output.metadata()?.file_type().is_file();
+    let mut output = BufWriter::new(output);
+
+    let mut decoder = Decoder::new();
+    let mut chunk = vec![0u8; CHUNK_LEN];
+    let mut buffer = vec![0u8; CHUNK_LEN];
+
+    let mut hole_len = 0;
+    let mut output_len = 0;
+
+    loop {
+        let chunk_len = input.read(&mut chunk)?;
+        let mut consumed = &chunk[..chunk_len];
+
+        let state = loop {
+            let (consumed_len, extent, state) = decoder.step_extent(consumed, &mut buffer);
+            consumed = &consumed[consumed_len..];
+
+            match extent {
+                Extent::Fill { byte: 0x00, len } if sparse => {
+                    hole_len += len;
+                }
+                Extent::Fill { byte, len } => {
+                    skip_hole(&mut output, &mut hole_len)?;
+                    write_fill(&mut output, byte, len)?;
+                }
+                Extent::Data(data) if !data.is_empty() => {
+                    skip_hole(&mut output, &mut hole_len)?;
+                    output.write_all(data)?;
+                }
+                Extent::Data(_) => {}
+            }
+
+            output_len += match extent {
+                Extent::Fill { len, .. } => len,
+                Extent::Data(data) => data.len() as u64,
+            };
+
+            if state != DecoderState::CanProduce {
+                break state;
+            }
+        };
+
+        match state {
+            DecoderState::CanConsume if chunk_len == 0 => {
+                return Err(invalid_data("compressed data is truncated"));
+            }
+            DecoderState::Terminated {
+                corrupted: true, ..
+            } => {
+                return Err(invalid_data("compressed data is not zero-padded"));
+            }
+            DecoderState::Terminated {
+                unaligned: true, ..
+            } => {
+                return Err(invalid_data(
+                    "decompressed data is not a whole number of bytes",
+                ));
+            }
+            DecoderState::Terminated { .. } => break,
+            _ => {}
+        }
+    }
+
+    let output = output
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)?;
+
+    if sparse {
+        output.set_len(output_len)?;
+    }
+
+    Ok(())
+}
+
+/// Derives code tables from a corpus of files and prints them as an implementation of `Code`.
+fn train(name: &str, inputs: &[String]) -> io::Result<()> {
+    let mut trainer = CodeTrainer::new();
+
+    for input in inputs {
+        trainer.add_sample(&fs::read(input)?);
+    }
+
+    let payload_bits = trainer.train();
+
+    eprintln!(
+        "estimated size: {} bits (default code: {} bits)",
+        trainer.estimate_bits(&payload_bits),
+        trainer.estimate_default_bits()
+    );
+
+    println!("/// Code tables trained on {} sample(s).", inputs.len());
+    println!("pub struct {name};");
+    println!();
+    println!("impl zerocrush::Code for {name} {{");
+    println!("    const PAYLOAD_BITS: [[u8; 12]; 2] = [");
+    println!("        {:?},", payload_bits[0]);
+    println!("        {:?},", payload_bits[1]);
+    println!("    ];");
+    println!("}}");
+
+    Ok(())
+}
+
+fn skip_hole(output: &mut BufWriter<File>, hole_len: &mut u64) -> io::Result<()> {
+    if *hole_len >= MIN_HOLE_LEN {
+        output.seek(SeekFrom::Current(*hole_len as i64))?;
+    } else {
+        write_fill(output, 0x00, *hole_len)?;
+    }
+
+    *hole_len = 0;
+
+    Ok(())
+}
+
+fn write_fill(output: &mut impl Write, byte: u8, mut len: u64) -> io::Result<()> {
+    let fill = [byte; 4096];
+
+    while len != 0 {
+        let chunk_len = len.min(fill.len() as u64) as usize;
+        output.write_all(&fill[..chunk_len])?;
+        len -= chunk_len as u64;
+    }
+
+    Ok(())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Returns the offset of the next data region at or after the given offset.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn seek_data(file: &mut File, offset: u64, file_len: u64) -> io::Result<u64> {
+    lseek(file, offset, libc::SEEK_DATA).map(|data| data.unwrap_or(file_len))
+}
+
+/// Returns the offset of the next hole at or after the given offset.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn seek_hole(file: &mut File, offset: u64, file_len: u64) -> io::Result<u64> {
+    lseek(file, offset, libc::SEEK_HOLE).map(|hole| hole.unwrap_or(file_len).min(file_len))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn lseek(file: &mut File, offset: u64, whence: libc::c_int) -> io::Result<Option<u64>> {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: `lseek` does not access any memory of this process, and the file descriptor is
+    // owned by `file`, which is borrowed for the whole call so that it remains open.
+    let result = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+
+    if result >= 0 {
+        return Ok(Some(result as u64));
+    }
+
+    let err = io::Error::last_os_error();
+
+    match err.raw_os_error() {
+        Some(libc::ENXIO) => Ok(None),
+        Some(libc::EINVAL) if whence == libc::SEEK_DATA => Ok(Some(offset)),
+        Some(libc::EINVAL) => Ok(None),
+        _ => Err(err),
+    }
+}
+
+/// Returns the offset of the next data region at or after the given offset.
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn seek_data(_: &mut File, offset: u64, _: u64) -> io::Result<u64> {
+    Ok(offset)
+}
+
+/// Returns the offset of the next hole at or after the given offset.
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn seek_hole(_: &mut File, _: u64, file_len: u64) -> io::Result<u64> {
+    Ok(file_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("zerocrush-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn sparse_round_trip() {
+        let original = temp_path("original");
+        let compressed = temp_path("compressed");
+        let decompressed = temp_path("decompressed");
+
+        // Data separated by a zero run shorter than a hole and by a hole, followed by a hole.
+        let mut file = File::create(&original).unwrap();
+        file.write_all(&[0xAB; 10]).unwrap();
+        file.write_all(&[0x00; 1000]).unwrap();
+        file.write_all(&[0xCD; 10]).unwrap();
+        file.seek(SeekFrom::Start(65536)).unwrap();
+        file.write_all(&[0xEF; 10]).unwrap();
+        file.set_len(200_000).unwrap();
+        drop(file);
+
+        let paths = [&original, &compressed, &decompressed].map(|path| path.to_str().unwrap());
+
+        compress(paths[0], paths[1]).unwrap();
+        decompress(paths[1], paths[2]).unwrap();
+
+        let data = fs::read(&original).unwrap();
+
+        let mut encoded = vec![0u8; 1024];
+        let encoded_len = zerocrush::encode_into_slice(&data, &mut encoded).unwrap();
+
+        assert_eq!(fs::read(&compressed).unwrap(), &encoded[..encoded_len]);
+        assert_eq!(fs::read(&decompressed).unwrap(), data);
+
+        for path in [original, compressed, decompressed] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
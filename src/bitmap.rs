@@ -0,0 +1,376 @@
+use crate::{DecodeSliceError, Encoder, Run, SymbolReader};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Owned bitmap held in encoded form, supporting queries and updates without being decoded.
+///
+/// Updating a bit only re-encodes the runs adjacent to it in place. Unless the length of these
+/// runs changes by a multiple of eight bits, the encoded data following them is then shifted into
+/// place, which takes time proportional to its length. Bitmaps constructed by this type are always encoded exactly as the
+/// encoder would have encoded their decoded data.
+#[derive(Clone, Debug)]
+pub struct CompressedBitmap {
+    data: Vec<u8>,
+    bit_len: u64,
+    len: u64,
+}
+
+impl CompressedBitmap {
+    /// Constructs a new bitmap of the given length in bits with every bit cleared.
+    pub fn new(len: u64) -> Self {
+        let mut writer = BitWriter::default();
+
+        if len != 0 {
+            encode_runs(false, &[Run { bit: false, len }], &mut writer);
+        }
+
+        for byte in [0b00000000, 0b00001111, 0b11111111] {
+            writer.push(byte, 8);
+        }
+
+        Self {
+            bit_len: writer.bits,
+            data: writer.data,
+            len,
+        }
+    }
+
+    /// Constructs a bitmap from encoded data, validating it in the process.
+    ///
+    /// Any bytes following the padded termination symbol are discarded.
+    pub fn from_encoded(mut data: Vec<u8>) -> Result<Self, DecodeSliceError> {
+        let mut spans = Spans::new(&data);
+        let mut len = 0;
+
+        for span in spans.by_ref() {
+            len += span?.len;
+        }
+
+        let bit_len = spans.bit_len;
+        data.truncate(bit_len.div_ceil(8) as usize);
+
+        Ok(Self { data, bit_len, len })
+    }
+
+    /// Returns the encoded data of this bitmap.
+    pub fn as_encoded(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Converts this bitmap into its encoded data.
+    pub fn into_encoded(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Returns the length of this bitmap in bits.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns whether this bitmap has a length of zero bits.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the value of the bit at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn get(&self, index: u64) -> bool {
+        self.locate(index).1.bit
+    }
+
+    /// Sets the bit at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn set(&mut self, index: u64) {
+        self.update(index, true);
+    }
+
+    /// Clears the bit at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn clear(&mut self, index: u64) {
+        self.update(index, false);
+    }
+
+    /// Returns an iterator over the indices of the set bits of this bitmap, in ascending order.
+    pub fn ones(&self) -> impl Iterator<Item = u64> + '_ {
+        crate::ones(&self.data).flatten()
+    }
+
+    /// Returns the spans preceding, containing and following the given index.
+    fn locate(&self, index: u64) -> (Option<Span>, Span, Option<Span>) {
+        assert!(index < self.len, "index out of bounds");
+
+        let mut spans = Spans::new(&self.data).flatten();
+        let mut prev = None;
+
+        for span in spans.by_ref() {
+            if index < span.start + span.len {
+                return (prev, span, spans.next());
+            }
+
+            prev = Some(span);
+        }
+
+        unreachable!("bitmap length is consistent with its encoded data");
+    }
+
+    fn update(&mut self, index: u64, bit: bool) {
+        let (prev, span, next) = self.locate(index);
+
+        if span.bit == bit {
+            return;
+        }
+
+        let offset = index - span.start;
+
+        let runs = [
+            prev.as_ref().map_or(Run { bit, len: 0 }, Span::run),
+            Run {
+                bit: span.bit,
+                len: offset,
+            },
+            Run { bit, len: 1 },
+            Run {
+                bit: span.bit,
+                len: span.len - offset - 1,
+            },
+            next.as_ref().map_or(Run { bit, len: 0 }, Span::run),
+        ];
+
+        let start = prev
+            .as_ref()
+            .map_or(span.bits.start, |prev| prev.bits.start);
+        let end = next.as_ref().map_or(span.bits.end, |next| next.bits.end);
+        let mode = start != 0 && runs[0].bit;
+
+        let head = start / 8 * 8;
+        let mut tail = end.next_multiple_of(8).min(self.bit_len);
+
+        let mut writer = BitWriter::default();
+
+        writer.copy(&self.data, head..start);
+        encode_runs(mode, &runs, &mut writer);
+        writer.copy(&self.data, end..tail);
+
+        if writer.bits % 8 != 0 {
+            writer.copy(&self.data, tail..self.bit_len);
+            tail = self.bit_len;
+        }
+
+        let range = (head / 8) as usize..tail.div_ceil(8) as usize;
+
+        self.bit_len = self.bit_len - (tail - head) + writer.bits;
+        self.data.splice(range, writer.data);
+    }
+}
+
+/// Encodes runs following a symbol decoded in the opposite of the given mode, ending with the
+/// symbol completing the last run. The encoded symbols are not padded in any way.
+fn encode_runs(mode: bool, runs: &[Run], writer: &mut BitWriter) {
+    let mut encoder = Encoder::new();
+    encoder.queued_mode = mode;
+
+    let mut drain = |encoder: &mut Encoder| {
+        let mut buffer = [0u8; 4];
+
+        loop {
+            let mut buffer_len = 0;
+            encoder.produce(&mut buffer, &mut buffer_len);
+
+            for &byte in &buffer[..buffer_len] {
+                writer.push(byte, 8);
+            }
+
+            if !encoder.queued_done {
+                break;
+            }
+        }
+    };
+
+    for run in runs.iter().filter(|run| run.len != 0) {
+        if run.bit != encoder.queued_mode {
+            encoder.queued_mode = run.bit;
+            encoder.queued_done = true;
+            drain(&mut encoder);
+        }
+
+        let mut len = run.len;
+
+        while len != 0 {
+            if encoder.queued_bits == usize::MAX {
+                // The queued run is too long to be counted on this target, so part of it is
+                // produced as a continuation symbol.
+                let mode = encoder.queued_mode;
+                encoder.queued_bits = encoder.produce_run(mode, usize::MAX).unwrap();
+                drain(&mut encoder);
+            }
+
+            let amount = len.min((usize::MAX - encoder.queued_bits) as u64);
+
+            encoder.queued_bits += amount as usize;
+            len -= amount;
+        }
+    }
+
+    encoder.queued_mode = !encoder.queued_mode;
+    encoder.queued_done = true;
+    drain(&mut encoder);
+
+    writer.push((encoder.symbol_data >> 24) as u8, encoder.symbol_bits);
+}
+
+/// Maximal run of a bitmap along with the range of bits of the symbols encoding it.
+///
+/// Symbols representing no data are included in the span of the run preceding them, if any.
+#[derive(Clone, Debug)]
+struct Span {
+    bit: bool,
+    start: u64,
+    len: u64,
+    bits: Range<u64>,
+}
+
+impl Span {
+    fn run(&self) -> Run {
+        Run {
+            bit: self.bit,
+            len: self.len,
+        }
+    }
+}
+
+/// Iterator over the spans of encoded data held in a byte slice.
+struct Spans<'a> {
+    reader: SymbolReader<'a>,
+    span: Option<Span>,
+    offset: u64,
+    bit_len: u64,
+    done: bool,
+}
+
+impl<'a> Spans<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Self {
+            reader: SymbolReader::new(input),
+            span: None,
+            offset: 0,
+            bit_len: 0,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for Spans<'_> {
+    type Item = Result<Span, DecodeSliceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let bit = self.reader.mode();
+
+            let symbol = match self.reader.next_symbol() {
+                Ok(symbol) => symbol,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            };
+
+            let bit_pos = self.reader.bit_pos();
+
+            if symbol.term {
+                self.done = true;
+                self.bit_len = bit_pos;
+
+                if !self.reader.is_padded() {
+                    return Some(Err(DecodeSliceError::Corrupted));
+                }
+
+                break;
+            }
+
+            let count = symbol.count as u64;
+            self.offset += count;
+
+            match &mut self.span {
+                Some(span) if span.bit == bit || count == 0 => {
+                    span.len += count;
+                    span.bits.end = bit_pos;
+                }
+                _ if count == 0 => {}
+                span => {
+                    let start = span.as_ref().map_or(0, |span| span.bits.end);
+
+                    let next = Span {
+                        bit,
+                        start: self.offset - count,
+                        len: count,
+                        bits: start..bit_pos,
+                    };
+
+                    if let Some(span) = span.replace(next) {
+                        return Some(Ok(span));
+                    }
+                }
+            }
+        }
+
+        self.span.take().map(Ok)
+    }
+}
+
+/// Helper to append bits to a byte vector, MSB first.
+#[derive(Default)]
+struct BitWriter {
+    data: Vec<u8>,
+    bits: u64,
+}
+
+impl BitWriter {
+    /// Appends the given number of most significant bits of a byte.
+    fn push(&mut self, byte: u8, len: usize) {
+        let byte = byte & (0xFF00u16 >> len) as u8;
+        let shift = (self.bits % 8) as usize;
+
+        if shift == 0 {
+            if len != 0 {
+                self.data.push(byte);
+            }
+        } else {
+            *self.data.last_mut().unwrap() |= byte >> shift;
+
+            if shift + len > 8 {
+                self.data.push(byte << (8 - shift));
+            }
+        }
+
+        self.bits += len as u64;
+    }
+
+    /// Appends the given range of bits of a byte slice.
+    fn copy(&mut self, data: &[u8], range: Range<u64>) {
+        let mut bit_pos = range.start;
+
+        while bit_pos < range.end {
+            let index = (bit_pos / 8) as usize;
+            let shift = (bit_pos % 8) as u32;
+
+            let mut byte = data[index] << shift;
+
+            if shift != 0 {
+                byte |= data.get(index + 1).map_or(0, |next| next >> (8 - shift));
+            }
+
+            let len = (range.end - bit_pos).min(8);
+            self.push(byte, len as usize);
+            bit_pos += len;
+        }
+    }
+}
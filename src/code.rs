@@ -0,0 +1,59 @@
+use core::marker::PhantomData;
+
+/// Description of the prefix code tables used to represent runs of zeroes (mode 0) and runs of
+/// ones (mode 1), as described in the README.
+///
+/// In each mode, the twelve shortest symbols consist of between zero and eleven zero bits and a
+/// one bit followed by a payload, and each represent a range of run lengths as wide as their
+/// payload allows, in order of increasing run lengths starting from a single bit. The longest
+/// symbols (beginning with twelve zero bits) and the special symbols are common to all codes.
+pub trait Code {
+    /// The payload length in bits of the twelve shortest symbols of each mode, indexed by mode
+    /// and then by the number of leading zero bits of the symbol.
+    ///
+    /// No symbol may be longer than 24 bits, that is the payload length of the symbol with `n`
+    /// leading zero bits may not exceed `23 - n` bits.
+    const PAYLOAD_BITS: [[u8; 12]; 2];
+}
+
+/// The prefix code tables described in the README, used unless otherwise specified.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DefaultCode;
+
+impl Code for DefaultCode {
+    const PAYLOAD_BITS: [[u8; 12]; 2] = [
+        [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    ];
+}
+
+/// Run lengths represented by the symbols of a code, derived from its payload lengths.
+pub(crate) struct Tables<C>(PhantomData<C>);
+
+impl<C: Code> Tables<C> {
+    /// The shortest run length represented by each symbol of each mode, followed by that of the
+    /// longest symbols.
+    pub const BASES: [[usize; 13]; 2] = {
+        let mut bases = [[1; 13]; 2];
+        let mut mode = 0;
+
+        while mode < 2 {
+            let mut index = 0;
+
+            while index < 12 {
+                let payload_bits = C::PAYLOAD_BITS[mode][index] as usize;
+                assert!(
+                    index + 1 + payload_bits <= 24,
+                    "symbol is longer than 24 bits"
+                );
+
+                bases[mode][index + 1] = bases[mode][index] + (1 << payload_bits);
+                index += 1;
+            }
+
+            mode += 1;
+        }
+
+        bases
+    };
+}
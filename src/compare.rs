@@ -0,0 +1,78 @@
+use crate::{DecodeSliceError, RunPairs};
+use core::ops::Range;
+
+/// Iterator over the ranges of bit positions at which the decoded data of two encoded inputs
+/// differ, returned by [`diff`].
+///
+/// Bit positions past the end of the decoded data of the shorter input are considered to differ.
+/// Adjacent differing bits are merged into a single range, and iteration stops after the first
+/// error.
+#[derive(Clone, Debug)]
+pub struct Diff<'a> {
+    pairs: RunPairs<'a>,
+    offset: u64,
+    pending: Option<Range<u64>>,
+    done: bool,
+}
+
+impl Iterator for Diff<'_> {
+    type Item = Result<Range<u64>, DecodeSliceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let (len, first_bit, second_bit) = match self.pairs.next() {
+                Some(Ok(pair)) => pair,
+                Some(Err(err)) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+                None => {
+                    self.done = true;
+                    break;
+                }
+            };
+
+            let start = self.offset;
+            self.offset += len;
+
+            if first_bit != second_bit {
+                match &mut self.pending {
+                    Some(range) => range.end = self.offset,
+                    None => self.pending = Some(start..self.offset),
+                }
+            } else if let Some(range) = self.pending.take() {
+                return Some(Ok(range));
+            }
+        }
+
+        self.pending.take().map(Ok)
+    }
+}
+
+/// Returns an iterator over the ranges of bit positions at which the decoded data of two encoded
+/// inputs differ, without decoding either into bytes.
+///
+/// Both inputs must be encoded in [`Format::V1`](crate::Format::V1) using the
+/// [`DefaultCode`](crate::DefaultCode).
+pub fn diff<'a>(first: &'a [u8], second: &'a [u8]) -> Diff<'a> {
+    Diff {
+        pairs: RunPairs::new(first, second),
+        offset: 0,
+        pending: None,
+        done: false,
+    }
+}
+
+/// Checks whether two encoded inputs decode to the same data, regardless of how that data was
+/// encoded.
+///
+/// The encoded inputs are only read up to their first difference, so that any errors located after
+/// it are not reported. Both inputs must be encoded in [`Format::V1`](crate::Format::V1) using the
+/// [`DefaultCode`](crate::DefaultCode).
+pub fn compare(first: &[u8], second: &[u8]) -> Result<bool, DecodeSliceError> {
+    match diff(first, second).next() {
+        Some(Ok(_)) => Ok(false),
+        Some(Err(err)) => Err(err),
+        None => Ok(true),
+    }
+}
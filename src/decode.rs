@@ -0,0 +1,303 @@
+use crate::{DecoderState, Extent};
+use core::fmt::Debug;
+
+/// Bits repeated throughout a run queued for output by a decoder.
+pub(crate) trait Pattern: Copy + Debug {
+    /// Returns the mode of the run if all of its bits are identical.
+    fn mode(self) -> Option<bool>;
+
+    /// Returns the value of every whole output byte covered by the run if they are all identical.
+    fn fill_byte(self) -> Option<u8>;
+
+    /// Advances past the given number of bits of the run (at most eight), returning them
+    /// right-aligned.
+    fn next_bits(&mut self, amount: usize) -> u8;
+
+    /// Advances past the given number of bits of the run without returning them.
+    fn skip_bits(&mut self, amount: u64);
+}
+
+/// Runs of identical bits, represented by their mode.
+impl Pattern for bool {
+    fn mode(self) -> Option<bool> {
+        Some(self)
+    }
+
+    fn fill_byte(self) -> Option<u8> {
+        Some(if self { 0xFF } else { 0x00 })
+    }
+
+    fn next_bits(&mut self, amount: usize) -> u8 {
+        if *self {
+            ((1u16 << amount) - 1) as u8
+        } else {
+            0
+        }
+    }
+
+    fn skip_bits(&mut self, _: u64) {}
+}
+
+/// Runs repeating a 32-bit word, beginning with its most significant bit.
+impl Pattern for u32 {
+    fn mode(self) -> Option<bool> {
+        match self {
+            0 => Some(false),
+            u32::MAX => Some(true),
+            _ => None,
+        }
+    }
+
+    fn fill_byte(self) -> Option<u8> {
+        (self.rotate_left(8) == self).then_some(self as u8)
+    }
+
+    fn next_bits(&mut self, amount: usize) -> u8 {
+        *self = self.rotate_left(amount as u32);
+        (*self & ((1 << amount) - 1)) as u8
+    }
+
+    fn skip_bits(&mut self, amount: u64) {
+        *self = self.rotate_left((amount % 32) as u32);
+    }
+}
+
+/// Decoder of the symbols of encoded data into runs of type `P`, driven by a [`RunDecoder`].
+pub(crate) trait SymbolDecoder<P> {
+    /// Decodes the next symbols, queuing at most one run, returning `true` if more input is
+    /// required first. It is only called while no run is queued and the data has not terminated.
+    fn decode(
+        &mut self,
+        consumed: &[u8],
+        consumed_len: &mut usize,
+        queued_bits: &mut usize,
+        queued_data: &mut P,
+    ) -> bool;
+
+    /// Returns whether the encoded data has terminated.
+    fn is_terminated(&self) -> bool;
+
+    /// Returns whether the terminated encoded data is corrupted.
+    fn is_corrupted(&self) -> bool;
+
+    /// Returns the mode of the next run to be decoded if it is certain to be a run of identical
+    /// bits in that mode, unless no run is decoded at all.
+    fn next_mode(&self) -> Option<bool>;
+}
+
+/// Decoder state shared by the streaming decoders, producing the runs decoded by the symbol
+/// decoder `S` into output bytes.
+#[derive(Debug)]
+pub(crate) struct RunDecoder<S, P> {
+    symbols: S,
+    queued_bits: usize,
+    queued_data: P,
+    output_data: u8,
+    output_bits: u8,
+}
+
+impl<S, P> RunDecoder<S, P> {
+    /// Constructs a new run decoder in its initial state, with the given symbol decoder and
+    /// (empty) queued run.
+    pub const fn new(symbols: S, queued_data: P) -> Self {
+        Self {
+            symbols,
+            queued_bits: 0,
+            queued_data,
+            output_data: 0,
+            output_bits: 0,
+        }
+    }
+}
+
+impl<P: Pattern, S: SymbolDecoder<P>> RunDecoder<S, P> {
+    /// Steps this decoder, returning a `(bytes consumed, bytes produced, state)` tuple.
+    pub fn step(&mut self, consumed: &[u8], produced: &mut [u8]) -> (usize, usize, DecoderState) {
+        let mut consumed_len = 0;
+        let mut produced_len = 0;
+
+        loop {
+            if self.consume(consumed, &mut consumed_len) {
+                return (consumed_len, produced_len, DecoderState::CanConsume);
+            }
+
+            if self.produce(produced, &mut produced_len) {
+                return (consumed_len, produced_len, DecoderState::CanProduce);
+            }
+
+            if self.symbols.is_terminated() {
+                break;
+            }
+        }
+
+        (consumed_len, produced_len, self.terminated_state())
+    }
+
+    /// Steps this decoder, reporting runs covering whole output bytes as [`Extent::Fill`],
+    /// returning a `(bytes consumed, extent, state)` tuple.
+    pub fn step_extent<'a>(
+        &mut self,
+        consumed: &[u8],
+        produced: &'a mut [u8],
+    ) -> (usize, Extent<'a>, DecoderState) {
+        let mut consumed_len = 0;
+        let mut produced_len = 0;
+
+        let state = loop {
+            if self.consume(consumed, &mut consumed_len) {
+                break DecoderState::CanConsume;
+            }
+
+            if self.output_bits == 0
+                && self.queued_bits < 8
+                && self.queued_bits != 0
+                && self.queued_data.mode().is_some()
+                && self.queued_data.mode() == self.symbols.next_mode()
+                && !self.symbols.is_terminated()
+            {
+                let queued_bits = core::mem::take(&mut self.queued_bits);
+                let needs_input = self.consume(consumed, &mut consumed_len);
+                self.queued_bits += queued_bits;
+
+                if needs_input {
+                    break DecoderState::CanConsume;
+                }
+            }
+
+            if self.output_bits == 0 && self.queued_bits >= 8 {
+                if let Some(byte) = self.queued_data.fill_byte() {
+                    if produced_len != 0 {
+                        break DecoderState::CanProduce;
+                    }
+
+                    let len = (self.queued_bits / 8) as u64;
+
+                    self.queued_bits %= 8;
+
+                    return (
+                        consumed_len,
+                        Extent::Fill { byte, len },
+                        DecoderState::CanProduce,
+                    );
+                }
+            }
+
+            if self.produce(produced, &mut produced_len) {
+                break DecoderState::CanProduce;
+            }
+
+            if self.symbols.is_terminated() {
+                break self.terminated_state();
+            }
+        };
+
+        let produced: &'a [u8] = produced;
+
+        (consumed_len, Extent::Data(&produced[..produced_len]), state)
+    }
+
+    /// Skips up to the given number of decoded bits without producing them, returning a
+    /// `(bytes consumed, bits skipped, state)` tuple.
+    pub fn skip(&mut self, consumed: &[u8], bits: u64) -> (usize, u64, DecoderState) {
+        let mut consumed_len = 0;
+
+        let amount = bits.min(self.output_bits as u64) as u8;
+        self.output_bits -= amount;
+        self.output_data &= ((1u16 << self.output_bits) - 1) as u8;
+
+        let mut skipped = amount as u64;
+
+        let state = loop {
+            let amount = (bits - skipped).min(self.queued_bits as u64);
+            self.queued_bits -= amount as usize;
+            self.queued_data.skip_bits(amount);
+            skipped += amount;
+
+            if self.symbols.is_terminated() && self.queued_bits == 0 && self.output_bits != 8 {
+                break self.terminated_state();
+            }
+
+            if skipped == bits {
+                break DecoderState::CanProduce;
+            }
+
+            if self.consume(consumed, &mut consumed_len) {
+                break DecoderState::CanConsume;
+            }
+        };
+
+        (consumed_len, skipped, state)
+    }
+
+    /// Retrieves the (right-aligned) last partial output byte.
+    pub fn partial_output_byte(&self) -> Option<(u8, usize)> {
+        if self.symbols.is_terminated() && self.output_bits != 0 {
+            Some((self.output_data, self.output_bits as usize))
+        } else {
+            None
+        }
+    }
+
+    fn terminated_state(&self) -> DecoderState {
+        debug_assert!(self.queued_bits == 0);
+
+        DecoderState::Terminated {
+            corrupted: self.symbols.is_corrupted(),
+            unaligned: self.output_bits != 0,
+        }
+    }
+
+    fn consume(&mut self, consumed: &[u8], consumed_len: &mut usize) -> bool {
+        if self.queued_bits != 0 || self.symbols.is_terminated() {
+            return false;
+        }
+
+        self.symbols.decode(
+            consumed,
+            consumed_len,
+            &mut self.queued_bits,
+            &mut self.queued_data,
+        )
+    }
+
+    fn produce(&mut self, produced: &mut [u8], produced_len: &mut usize) -> bool {
+        if self.output_bits == 0 && self.queued_bits >= 8 {
+            let transfer = (self.queued_bits / 8).min(produced.len() - *produced_len);
+
+            if transfer == 0 {
+                return true;
+            }
+
+            let slice = &mut produced[*produced_len..][..transfer];
+
+            if let Some(byte) = self.queued_data.fill_byte() {
+                slice.fill(byte);
+            } else {
+                for byte in slice {
+                    *byte = self.queued_data.next_bits(8);
+                }
+            }
+
+            self.queued_bits -= transfer * 8;
+            *produced_len += transfer;
+        } else if self.output_bits != 8 && self.queued_bits != 0 {
+            let amount = (8 - self.output_bits as usize).min(self.queued_bits);
+
+            self.output_data <<= amount;
+            self.output_data |= self.queued_data.next_bits(amount);
+            self.output_bits += amount as u8;
+            self.queued_bits -= amount;
+        } else if self.output_bits == 8 {
+            if let Some(byte) = produced.get_mut(*produced_len) {
+                *byte = self.output_data;
+                self.output_data = 0;
+                self.output_bits = 0;
+                *produced_len += 1;
+            } else {
+                return true;
+            }
+        }
+
+        false
+    }
+}
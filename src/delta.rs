@@ -0,0 +1,158 @@
+use crate::{DecodeSliceError, Decoder, DecoderState, EncodeSliceError, Encoder, EncoderState};
+
+/// Number of bytes of the target or reference image processed at a time on the stack.
+const CHUNK_LEN: usize = 64;
+
+/// Source of the reference image against which a patch produced by [`encode_delta`] is decoded.
+pub trait ReadReference {
+    /// The type of errors reported while reading the reference image.
+    type Error;
+
+    /// Reads the bytes of the reference image starting at the given offset into the buffer, with
+    /// bytes past the end of the reference image reading as zero.
+    fn read(&mut self, offset: u64, buffer: &mut [u8]) -> Result<(), Self::Error>;
+}
+
+impl ReadReference for &[u8] {
+    type Error = core::convert::Infallible;
+
+    fn read(&mut self, offset: u64, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let bytes = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| self.get(offset..))
+            .unwrap_or(&[]);
+        let len = bytes.len().min(buffer.len());
+
+        buffer[..len].copy_from_slice(&bytes[..len]);
+        buffer[len..].fill(0);
+
+        Ok(())
+    }
+}
+
+/// Errors that may occur while decoding a patch against a reference image.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DeltaDecodeError<E> {
+    /// The reference image could not be read.
+    Reference(E),
+    /// The patch could not be decoded.
+    Decode(DecodeSliceError),
+}
+
+/// Streaming decoder applying a patch produced by [`encode_delta`], combining the decoded bytes
+/// with the reference image as they are produced.
+///
+/// The decoder must not be used any further once an error has been reported.
+#[derive(Debug)]
+pub struct DeltaDecoder<R> {
+    decoder: Decoder,
+    reference: R,
+    offset: u64,
+}
+
+impl<R: ReadReference> DeltaDecoder<R> {
+    /// Constructs a new delta decoder against the given reference image.
+    pub const fn new(reference: R) -> Self {
+        Self {
+            decoder: Decoder::new(),
+            reference,
+            offset: 0,
+        }
+    }
+
+    /// Steps this delta decoder instance like [`Decoder::step`], returning a `(bytes consumed,
+    /// bytes produced, state)` tuple.
+    pub fn step(
+        &mut self,
+        consumed: &[u8],
+        produced: &mut [u8],
+    ) -> Result<(usize, usize, DecoderState), R::Error> {
+        let (consumed_len, produced_len, state) = self.decoder.step(consumed, produced);
+
+        let mut buffer = [0u8; CHUNK_LEN];
+
+        for chunk in produced[..produced_len].chunks_mut(CHUNK_LEN) {
+            let reference = &mut buffer[..chunk.len()];
+            self.reference.read(self.offset, reference)?;
+
+            for (byte, &reference) in chunk.iter_mut().zip(reference.iter()) {
+                *byte ^= reference;
+            }
+
+            self.offset += chunk.len() as u64;
+        }
+
+        Ok((consumed_len, produced_len, state))
+    }
+}
+
+/// Convenient helper function to encode the difference between a target image and a reference
+/// image into a destination byte slice, returning the length of the patch in bytes.
+///
+/// The patch is the encoded data of the bitwise XOR of both images, where the reference image is
+/// extended with zero bytes to the length of the target image if shorter, so it is very small if
+/// the images only differ in a few bits.
+pub fn encode_delta(
+    reference: &[u8],
+    target: &[u8],
+    output: &mut [u8],
+) -> Result<usize, EncodeSliceError> {
+    let mut encoder = Encoder::new();
+    let mut produced_len = 0;
+
+    let mut buffer = [0u8; CHUNK_LEN];
+
+    for (index, chunk) in target.chunks(CHUNK_LEN).enumerate() {
+        let delta = &mut buffer[..chunk.len()];
+        delta.copy_from_slice(chunk);
+
+        let reference = reference.get(index * CHUNK_LEN..).unwrap_or(&[]);
+
+        for (byte, &reference) in delta.iter_mut().zip(reference.iter()) {
+            *byte ^= reference;
+        }
+
+        let (_, len, state) = encoder.step(delta, &mut output[produced_len..]);
+        produced_len += len;
+
+        if state == EncoderState::CanProduce {
+            return Err(EncodeSliceError::NeedsMoreSpace);
+        }
+    }
+
+    encoder.set_consumed_bytes_end();
+
+    let (_, len, state) = encoder.step(&[], &mut output[produced_len..]);
+
+    match state {
+        EncoderState::CanConsume => unreachable!("is given entire input"),
+        EncoderState::CanProduce => Err(EncodeSliceError::NeedsMoreSpace),
+        EncoderState::Terminated => Ok(produced_len + len),
+    }
+}
+
+/// Convenient helper function to directly decode a patch produced by [`encode_delta`] against a
+/// reference image into a destination byte slice, returning the length of the target image.
+pub fn decode_delta<R: ReadReference>(
+    reference: R,
+    patch: &[u8],
+    output: &mut [u8],
+) -> Result<usize, DeltaDecodeError<R::Error>> {
+    let mut decoder = DeltaDecoder::new(reference);
+
+    let (_, produced_len, state) = decoder
+        .step(patch, output)
+        .map_err(DeltaDecodeError::Reference)?;
+
+    match state {
+        DecoderState::CanConsume => Err(DeltaDecodeError::Decode(DecodeSliceError::TruncatedInput)),
+        DecoderState::CanProduce => Err(DeltaDecodeError::Decode(DecodeSliceError::NeedsMoreSpace)),
+        DecoderState::Terminated {
+            corrupted: true, ..
+        } => Err(DeltaDecodeError::Decode(DecodeSliceError::Corrupted)),
+        DecoderState::Terminated {
+            unaligned: true, ..
+        } => Err(DeltaDecodeError::Decode(DecodeSliceError::Unaligned)),
+        DecoderState::Terminated { .. } => Ok(produced_len),
+    }
+}
@@ -0,0 +1,496 @@
+use crate::decode::{RunDecoder, SymbolDecoder};
+use crate::history::{BackrefPhase, RunHistory, HISTORY_BITS};
+use crate::pattern::PatternPhase;
+use crate::rice::RiceParams;
+use crate::symbol::Symbol;
+use crate::{Code, DecoderState, DefaultCode, Extent, Tables};
+use core::marker::PhantomData;
+
+/// Streaming decoder context for [`Format::V2`](crate::Format::V2), including the optional
+/// extensions of that format, namely adaptive coding, pattern symbols and back-references,
+/// decoding symbols from the code `C`.
+///
+/// Encoded data in [`Format::V1`](crate::Format::V1) is decoded by a [`Decoder`](crate::Decoder)
+/// instead, which requires much less state.
+#[derive(Debug)]
+pub struct ExtendedDecoder<C = DefaultCode> {
+    inner: RunDecoder<ExtendedSymbols<C>, u32>,
+}
+
+impl ExtendedDecoder {
+    /// Constructs a new decoder instance in its initial state.
+    pub const fn new() -> Self {
+        Self::with_code()
+    }
+}
+
+impl<C: Code> ExtendedDecoder<C> {
+    /// Constructs a new decoder instance in its initial state, decoding symbols from the code `C`
+    /// instead of the default code.
+    pub const fn with_code() -> Self {
+        Self {
+            inner: RunDecoder::new(ExtendedSymbols::new(), 0),
+        }
+    }
+
+    /// Resets this decoder instance to its initial state.
+    pub fn reset(&mut self) {
+        *self = Self::with_code();
+    }
+
+    /// Steps this decoder instance, returning a `(bytes consumed, bytes produced, state)` tuple.
+    pub fn step(&mut self, consumed: &[u8], produced: &mut [u8]) -> (usize, usize, DecoderState) {
+        self.inner.step(consumed, produced)
+    }
+
+    /// Steps this decoder instance like [`ExtendedDecoder::step`], except that runs covering whole
+    /// output bytes are reported as [`Extent::Fill`] instead of being written into the produced
+    /// slice, returning a `(bytes consumed, extent, state)` tuple.
+    ///
+    /// Decoded bytes are reported as [`Extent::Data`], possibly empty if no bytes were produced.
+    pub fn step_extent<'a>(
+        &mut self,
+        consumed: &[u8],
+        produced: &'a mut [u8],
+    ) -> (usize, Extent<'a>, DecoderState) {
+        self.inner.step_extent(consumed, produced)
+    }
+
+    /// Skips up to the given number of decoded bits without producing them, returning a
+    /// `(bytes consumed, bits skipped, state)` tuple.
+    ///
+    /// Fewer bits than requested are skipped only if more input is required or if the decoded
+    /// data terminates first. Decoded bits following the skipped ones are produced by subsequent
+    /// steps as if they started at the beginning of the output, so skipping a number of bits that
+    /// is not a multiple of eight shifts the alignment of the output bytes.
+    pub fn skip(&mut self, consumed: &[u8], bits: u64) -> (usize, u64, DecoderState) {
+        self.inner.skip(consumed, bits)
+    }
+
+    /// Retrieves the (right-aligned) last partial output byte.
+    pub fn partial_output_byte(&self) -> Option<(u8, usize)> {
+        self.inner.partial_output_byte()
+    }
+}
+
+impl Default for ExtendedDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the pattern repeated by a run of identical bits in the given mode.
+const fn run_data(mode: bool) -> u32 {
+    if mode {
+        u32::MAX
+    } else {
+        0
+    }
+}
+
+/// Symbol decoder of an [`ExtendedDecoder`], decoding the symbols of
+/// [`Format::V2`](crate::Format::V2) into runs and patterns.
+#[derive(Debug)]
+struct ExtendedSymbols<C> {
+    symbol_bits: usize,
+    ext_bits: usize,
+    replay_distance: usize,
+    replay_count: usize,
+
+    symbol_data: u32,
+    ext_data: usize,
+    pattern_data: u32,
+
+    rice: RiceParams,
+    pattern: PatternPhase,
+    backref: BackrefPhase,
+    history: RunHistory,
+
+    symbol_mode: bool,
+    symbol_term: bool,
+    symbol_head: bool,
+    symbol_fail: bool,
+    ext_prefix: bool,
+    adaptive: bool,
+    patterns: bool,
+    backrefs: bool,
+    pattern_word: bool,
+
+    code: PhantomData<C>,
+}
+
+impl<C: Code> ExtendedSymbols<C> {
+    const fn new() -> Self {
+        Self {
+            symbol_bits: 0,
+            ext_bits: 0,
+            replay_distance: 0,
+            replay_count: 0,
+
+            symbol_data: 0,
+            ext_data: 0,
+            pattern_data: 0,
+
+            rice: RiceParams::new(),
+            pattern: PatternPhase::Idle,
+            backref: BackrefPhase::Idle,
+            history: RunHistory::new(),
+
+            symbol_mode: false,
+            symbol_term: false,
+            symbol_head: true,
+            symbol_fail: false,
+            ext_prefix: false,
+            adaptive: false,
+            patterns: false,
+            backrefs: false,
+            pattern_word: false,
+
+            code: PhantomData,
+        }
+    }
+
+    /// Returns whether enough bits are buffered to parse the next part of the encoded data.
+    ///
+    /// With the code tables, this is always 24 bits, which are available following any symbol as
+    /// the termination symbol is itself 24 bits long. The symbols of the adaptive code can be as
+    /// short as 15 bits, so only the bits required by the next symbol are requested.
+    fn symbol_ready(&self) -> bool {
+        if !self.adaptive {
+            return self.symbol_bits >= 24;
+        }
+
+        if self.pattern == PatternPhase::Head {
+            return self.symbol_bits >= 2;
+        }
+
+        if self.ext_prefix {
+            return self.symbol_bits != 0;
+        }
+
+        if self.ext_bits != 0 {
+            return self.symbol_bits >= self.ext_bits.min(24);
+        }
+
+        let zeros = (self.symbol_data.leading_zeros() as usize).min(self.symbol_bits);
+
+        if zeros >= 12 {
+            self.symbol_bits >= 15
+        } else if zeros < self.symbol_bits {
+            self.symbol_bits >= zeros + 1 + self.rice.param(self.symbol_mode)
+        } else {
+            false
+        }
+    }
+
+    fn fail(&mut self) {
+        self.symbol_term = true;
+        self.symbol_fail = true;
+    }
+}
+
+impl<C: Code> SymbolDecoder<u32> for ExtendedSymbols<C> {
+    fn decode(
+        &mut self,
+        consumed: &[u8],
+        consumed_len: &mut usize,
+        queued_bits: &mut usize,
+        queued_data: &mut u32,
+    ) -> bool {
+        while *queued_bits == 0 && !self.symbol_term {
+            if self.replay_count != 0 {
+                let Some((bit, len)) = self.history.get(self.replay_distance) else {
+                    self.fail();
+                    continue;
+                };
+
+                self.history.push(bit, len);
+                self.replay_count -= 1;
+
+                *queued_bits = len;
+                *queued_data = run_data(bit);
+                break;
+            }
+
+            while !self.symbol_ready() {
+                let Some(&input_byte) = consumed.get(*consumed_len) else {
+                    return true;
+                };
+
+                self.symbol_data |= (input_byte as u32) << (24 - self.symbol_bits);
+                self.symbol_bits += 8;
+                *consumed_len += 1;
+            }
+
+            if self.symbol_head {
+                self.symbol_head = false;
+                self.symbol_mode = self.symbol_data & (1 << 30) != 0;
+
+                let len = if self.symbol_data & (1 << 31) != 0 {
+                    self.adaptive = self.symbol_data & (1 << 29) != 0;
+                    self.patterns = self.symbol_data & (1 << 28) != 0;
+                    self.backrefs = self.symbol_data & (1 << 27) != 0;
+                    5
+                } else {
+                    2
+                };
+
+                self.symbol_bits -= len;
+                self.symbol_data <<= len;
+                continue;
+            }
+
+            if self.pattern == PatternPhase::Head {
+                self.symbol_mode = self.symbol_data & (1 << 31) != 0;
+                self.pattern_word = self.symbol_data & (1 << 30) != 0;
+                self.pattern = PatternPhase::Value;
+
+                self.symbol_bits -= 2;
+                self.symbol_data <<= 2;
+                self.ext_bits = if self.pattern_word { 32 } else { 8 };
+                continue;
+            }
+
+            if self.ext_prefix {
+                let zeros = (self.symbol_data.leading_zeros() as usize).min(self.symbol_bits);
+
+                self.ext_bits += zeros;
+                self.symbol_bits -= zeros;
+                self.symbol_data = self.symbol_data.checked_shl(zeros as u32).unwrap_or(0);
+
+                if self.ext_bits >= usize::BITS as usize {
+                    self.fail();
+                } else if self.symbol_bits != 0 {
+                    self.ext_prefix = false;
+                    self.ext_bits += 1;
+                }
+
+                continue;
+            }
+
+            if self.ext_bits != 0 {
+                let amount = self.ext_bits.min(24);
+
+                self.ext_data <<= amount;
+                self.ext_data |= (self.symbol_data >> (32 - amount)) as usize;
+                self.ext_bits -= amount;
+                self.symbol_bits -= amount;
+                self.symbol_data <<= amount;
+
+                if self.ext_bits == 0 {
+                    let ext_data = core::mem::take(&mut self.ext_data);
+
+                    if self.backref == BackrefPhase::Kind {
+                        if ext_data != 0 {
+                            self.backref = BackrefPhase::Head;
+                            self.ext_bits = 1 + HISTORY_BITS;
+                        } else {
+                            self.backref = BackrefPhase::Idle;
+                        }
+
+                        continue;
+                    }
+
+                    if self.backref == BackrefPhase::Head {
+                        self.symbol_mode = ext_data >> HISTORY_BITS != 0;
+                        self.replay_distance = (ext_data & ((1 << HISTORY_BITS) - 1)) + 1;
+                        self.backref = BackrefPhase::Count;
+                        self.ext_prefix = true;
+                        continue;
+                    }
+
+                    if self.backref == BackrefPhase::Count {
+                        self.replay_count = ext_data;
+                        self.backref = BackrefPhase::Idle;
+                        break;
+                    }
+
+                    if self.pattern == PatternPhase::Value {
+                        self.pattern_data = if self.pattern_word {
+                            ext_data as u32
+                        } else {
+                            ext_data as u32 * 0x0101_0101
+                        };
+
+                        self.pattern = PatternPhase::Count;
+                        self.ext_prefix = true;
+                        continue;
+                    }
+
+                    if self.pattern == PatternPhase::Count {
+                        let width = if self.pattern_word { 32 } else { 8 };
+
+                        match ext_data.checked_mul(width) {
+                            Some(bits) => {
+                                *queued_bits = bits;
+                                *queued_data = self.pattern_data;
+                            }
+                            None => self.fail(),
+                        }
+
+                        self.pattern = PatternPhase::Idle;
+                        continue;
+                    }
+
+                    let limit = if self.adaptive {
+                        (12 << self.rice.param(self.symbol_mode)) + 1
+                    } else {
+                        Tables::<C>::BASES[self.symbol_mode as usize][12] + 4092
+                    };
+
+                    match ext_data.checked_add(limit - 1) {
+                        Some(count) => {
+                            if self.adaptive {
+                                self.rice.update(self.symbol_mode, count);
+                            }
+
+                            if self.backrefs {
+                                self.history.push(self.symbol_mode, count);
+                            }
+
+                            *queued_bits = count;
+                            *queued_data = run_data(self.symbol_mode);
+                            self.symbol_mode = !self.symbol_mode;
+                        }
+                        None => self.fail(),
+                    }
+                }
+
+                continue;
+            }
+
+            if self.adaptive {
+                let zeros = self.symbol_data.leading_zeros() as usize;
+
+                if zeros < 12 {
+                    let param = self.rice.param(self.symbol_mode);
+                    let len = zeros + 1 + param;
+
+                    let remainder = (self.symbol_data << (zeros + 1))
+                        .checked_shr(32 - param as u32)
+                        .unwrap_or(0);
+
+                    let count = 1 + (zeros << param) + remainder as usize;
+
+                    self.symbol_bits -= len;
+                    self.symbol_data <<= len;
+                    self.rice.update(self.symbol_mode, count);
+
+                    if self.backrefs {
+                        self.history.push(self.symbol_mode, count);
+                    }
+
+                    *queued_bits = count;
+                    *queued_data = run_data(self.symbol_mode);
+                    self.symbol_mode = !self.symbol_mode;
+                    break;
+                }
+
+                match (self.symbol_data >> 17) & 0b111 {
+                    0b100..=0b111 => {
+                        self.symbol_bits -= 13;
+                        self.symbol_data <<= 13;
+                        self.ext_prefix = true;
+                    }
+                    0b010 | 0b011 => {
+                        self.symbol_bits -= 14;
+                        self.symbol_data <<= 14;
+                        self.symbol_mode = !self.symbol_mode;
+
+                        if self.backrefs {
+                            self.backref = BackrefPhase::Kind;
+                            self.ext_bits = 1;
+                            continue;
+                        }
+
+                        break;
+                    }
+                    0b001 => {
+                        self.symbol_bits -= 15;
+                        self.symbol_data <<= 15;
+                        self.symbol_term = true;
+                    }
+                    _ if !self.patterns => self.fail(),
+                    _ => {
+                        self.symbol_bits -= 15;
+                        self.symbol_data <<= 15;
+                        self.pattern = PatternPhase::Head;
+                    }
+                }
+
+                continue;
+            }
+
+            if self.symbol_data >> 20 == 0 {
+                match (self.symbol_data >> 8) & 0xFFF {
+                    0xFFC if !self.patterns => {
+                        self.fail();
+                        continue;
+                    }
+                    0xFFC => {
+                        self.symbol_bits -= 24;
+                        self.symbol_data <<= 24;
+                        self.pattern = PatternPhase::Head;
+                        continue;
+                    }
+                    0xFFD => {
+                        self.symbol_bits -= 24;
+                        self.symbol_data <<= 24;
+                        self.ext_prefix = true;
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
+            let symbol = Symbol::decode::<C>(self.symbol_data, self.symbol_mode);
+
+            self.symbol_bits -= symbol.len;
+            self.symbol_data <<= symbol.len;
+
+            if symbol.term {
+                self.symbol_term = true;
+            }
+
+            if symbol.count > 0 {
+                if self.backrefs {
+                    self.history.push(self.symbol_mode, symbol.count);
+                }
+
+                *queued_bits = symbol.count;
+                *queued_data = run_data(self.symbol_mode);
+            }
+
+            if !symbol.cont {
+                self.symbol_mode = !self.symbol_mode;
+            }
+
+            if self.backrefs && symbol.count == 0 && !symbol.term {
+                self.backref = BackrefPhase::Kind;
+                self.ext_bits = 1;
+                continue;
+            }
+
+            break;
+        }
+
+        false
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.symbol_term
+    }
+
+    fn is_corrupted(&self) -> bool {
+        debug_assert!(self.symbol_bits <= 7 || self.symbol_fail);
+
+        self.symbol_data != 0 || self.symbol_fail
+    }
+
+    /// Back-references replay runs of either mode, and pattern symbols or a mode change symbol
+    /// followed by its kind bit may be decoded before the next run, so no mode is reported when
+    /// any of these may be encountered.
+    fn next_mode(&self) -> Option<bool> {
+        (self.replay_count == 0 && !self.patterns && !self.backrefs).then_some(self.symbol_mode)
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{DecodeSliceError, Decoder, DecoderState};
+
+/// Portion of the decoded output reported by [`Decoder::step_extent`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Extent<'a> {
+    /// Run of identical bytes (either `0x00` or `0xFF`, or the byte repeated by a pattern symbol)
+    /// which was not written to the output.
+    Fill {
+        /// The value of every byte in the run.
+        byte: u8,
+        /// The number of bytes in the run.
+        len: u64,
+    },
+    /// Decoded bytes which were written to the output.
+    Data(&'a [u8]),
+}
+
+/// Helper to decode arbitrary data from a byte slice as a sequence of extents.
+///
+/// Adjacent fill extents of the same byte are merged together, so that arbitrarily long runs are
+/// reported as a single extent regardless of how many symbols they were encoded with.
+#[derive(Debug)]
+pub struct ExtentReader<'a> {
+    decoder: Decoder,
+    input: &'a [u8],
+    offset: u64,
+    pending: Option<(u8, u64)>,
+    done: bool,
+}
+
+impl<'a> ExtentReader<'a> {
+    /// Constructs a new extent reader over the given encoded data.
+    pub const fn new(input: &'a [u8]) -> Self {
+        Self {
+            decoder: Decoder::new(),
+            input,
+            offset: 0,
+            pending: None,
+            done: false,
+        }
+    }
+
+    /// Decodes the next extent using the given buffer, returning the extent along with its offset
+    /// in the decoded output or `None` once the encoded data has terminated.
+    pub fn next_extent<'b>(
+        &mut self,
+        buffer: &'b mut [u8],
+    ) -> Option<Result<(u64, Extent<'b>), DecodeSliceError>> {
+        if self.done {
+            return None;
+        }
+
+        let (extent, state) = match self.pending.take() {
+            Some((byte, len)) => (Extent::Fill { byte, len }, DecoderState::CanProduce),
+            None => {
+                let (consumed_len, extent, state) = self.decoder.step_extent(self.input, buffer);
+                self.input = &self.input[consumed_len..];
+
+                (extent, state)
+            }
+        };
+
+        let extent = match extent {
+            Extent::Fill { byte, mut len } => {
+                loop {
+                    let (consumed_len, next, _) = self.decoder.step_extent(self.input, &mut []);
+                    self.input = &self.input[consumed_len..];
+
+                    match next {
+                        Extent::Fill {
+                            byte: next_byte,
+                            len: next_len,
+                        } if next_byte == byte => {
+                            len += next_len;
+                        }
+                        Extent::Fill {
+                            byte: next_byte,
+                            len: next_len,
+                        } => {
+                            self.pending = Some((next_byte, next_len));
+                            break;
+                        }
+                        Extent::Data(_) => break,
+                    }
+                }
+
+                Extent::Fill { byte, len }
+            }
+            Extent::Data(data) if !data.is_empty() => extent,
+            Extent::Data(_) => {
+                self.done = true;
+
+                return match state {
+                    DecoderState::CanConsume => Some(Err(DecodeSliceError::TruncatedInput)),
+                    DecoderState::CanProduce => Some(Err(DecodeSliceError::NeedsMoreSpace)),
+                    DecoderState::Terminated {
+                        corrupted: true, ..
+                    } => Some(Err(DecodeSliceError::Corrupted)),
+                    DecoderState::Terminated {
+                        unaligned: true, ..
+                    } => Some(Err(DecodeSliceError::Unaligned)),
+                    DecoderState::Terminated { .. } => None,
+                };
+            }
+        };
+
+        let offset = self.offset;
+
+        self.offset += match extent {
+            Extent::Fill { len, .. } => len,
+            Extent::Data(data) => data.len() as u64,
+        };
+
+        Some(Ok((offset, extent)))
+    }
+}
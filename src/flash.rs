@@ -0,0 +1,216 @@
+use crate::{DecodeSliceError, Decoder, DecoderState, ReadReference};
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+
+/// Errors that may occur while decoding into a flash device.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FlashDecodeError<E> {
+    /// The flash device reported an error.
+    Flash(E),
+    /// The encoded data could not be decoded.
+    Decode(DecodeSliceError),
+    /// The destination offset is not aligned to the flash erase size.
+    Misaligned,
+}
+
+/// Streaming decoder programming its output directly into a NOR flash device.
+///
+/// Sectors are erased as the decoded image reaches them and writes are issued in multiples of the
+/// device write size from an internal buffer of `N` bytes, which must itself be a multiple of the
+/// write size. Write units which only contain erased (`0xFF`) bytes are never programmed. If the
+/// decoded image does not end on a byte boundary, the remaining bits of its last byte are left in
+/// the erased state, as is the remainder of the last write unit.
+#[derive(Debug)]
+pub struct FlashDecoder<'a, F, const N: usize> {
+    flash: &'a mut F,
+    decoder: Decoder,
+
+    buffer: [u8; N],
+    buffer_len: usize,
+
+    start: u32,
+    offset: u32,
+    erased: u32,
+
+    image_len: Option<usize>,
+}
+
+impl<'a, F: NorFlash, const N: usize> FlashDecoder<'a, F, N> {
+    /// Constructs a new flash decoder writing the decoded image at the given flash offset.
+    pub fn new(flash: &'a mut F, offset: u32) -> Result<Self, FlashDecodeError<F::Error>> {
+        assert!(N != 0 && N.is_multiple_of(F::WRITE_SIZE));
+
+        if !(offset as usize).is_multiple_of(F::ERASE_SIZE) {
+            return Err(FlashDecodeError::Misaligned);
+        }
+
+        Ok(Self {
+            flash,
+            decoder: Decoder::new(),
+
+            buffer: [0xFF; N],
+            buffer_len: 0,
+
+            start: offset,
+            offset,
+            erased: offset,
+
+            image_len: None,
+        })
+    }
+
+    /// Decodes the given bytes into flash, returning the length of the decoded image once the
+    /// encoded data has terminated, or `None` if more input bytes are required.
+    pub fn write(
+        &mut self,
+        mut consumed: &[u8],
+    ) -> Result<Option<usize>, FlashDecodeError<F::Error>> {
+        if self.image_len.is_some() {
+            return Ok(self.image_len);
+        }
+
+        loop {
+            let (consumed_len, produced_len, state) = self
+                .decoder
+                .step(consumed, &mut self.buffer[self.buffer_len..]);
+
+            consumed = &consumed[consumed_len..];
+            self.buffer_len += produced_len;
+
+            match state {
+                DecoderState::CanConsume => return Ok(None),
+                DecoderState::CanProduce => self.flush_buffer()?,
+                DecoderState::Terminated {
+                    corrupted: true, ..
+                } => {
+                    return Err(FlashDecodeError::Decode(DecodeSliceError::Corrupted));
+                }
+                DecoderState::Terminated { .. } => break,
+            }
+        }
+
+        let mut image_len = (self.offset - self.start) as usize + self.buffer_len;
+
+        if let Some((byte, bits)) = self.decoder.partial_output_byte() {
+            if self.buffer_len == N {
+                self.flush_buffer()?;
+            }
+
+            self.buffer[self.buffer_len] = (byte << (8 - bits)) | (0xFF >> bits);
+            self.buffer_len += 1;
+            image_len += 1;
+        }
+
+        let padded_len = self.buffer_len.next_multiple_of(F::WRITE_SIZE);
+        self.buffer[self.buffer_len..padded_len].fill(0xFF);
+        self.buffer_len = padded_len;
+
+        self.flush_buffer()?;
+        self.image_len = Some(image_len);
+
+        Ok(self.image_len)
+    }
+
+    fn flush_buffer(&mut self) -> Result<(), FlashDecodeError<F::Error>> {
+        let end = self.offset as usize + self.buffer_len;
+
+        if end > self.flash.capacity() {
+            return Err(FlashDecodeError::Decode(DecodeSliceError::NeedsMoreSpace));
+        }
+
+        while (self.erased as usize) < end {
+            let sector_end = self.erased + F::ERASE_SIZE as u32;
+
+            self.flash
+                .erase(self.erased, sector_end)
+                .map_err(FlashDecodeError::Flash)?;
+
+            self.erased = sector_end;
+        }
+
+        let units = self.buffer_len / F::WRITE_SIZE;
+        let mut unit = 0;
+
+        while unit < units {
+            if self.is_erased_unit(unit) {
+                unit += 1;
+                continue;
+            }
+
+            let first = unit;
+
+            while unit < units && !self.is_erased_unit(unit) {
+                unit += 1;
+            }
+
+            let bytes = &self.buffer[first * F::WRITE_SIZE..unit * F::WRITE_SIZE];
+
+            self.flash
+                .write(self.offset + (first * F::WRITE_SIZE) as u32, bytes)
+                .map_err(FlashDecodeError::Flash)?;
+        }
+
+        self.offset += self.buffer_len as u32;
+        self.buffer_len = 0;
+
+        Ok(())
+    }
+
+    fn is_erased_unit(&self, unit: usize) -> bool {
+        let bytes = &self.buffer[unit * F::WRITE_SIZE..][..F::WRITE_SIZE];
+
+        bytes.iter().all(|&byte| byte == 0xFF)
+    }
+}
+
+/// Convenient helper function to directly decode arbitrary data into a flash device at an offset.
+pub fn decode_into_flash<F: NorFlash, const N: usize>(
+    input: &[u8],
+    flash: &mut F,
+    offset: u32,
+) -> Result<usize, FlashDecodeError<F::Error>> {
+    let mut decoder = FlashDecoder::<F, N>::new(flash, offset)?;
+
+    match decoder.write(input)? {
+        Some(image_len) => Ok(image_len),
+        None => Err(FlashDecodeError::Decode(DecodeSliceError::TruncatedInput)),
+    }
+}
+
+/// Reference image of a given length held in a flash device at an offset, such as the installed
+/// image of an A/B update scheme, against which a [`DeltaDecoder`](crate::DeltaDecoder) decodes.
+///
+/// The flash device must support reads of any alignment, that is, have a read size of one byte.
+#[derive(Debug)]
+pub struct FlashReference<'a, F> {
+    flash: &'a mut F,
+    offset: u32,
+    len: u32,
+}
+
+impl<'a, F: ReadNorFlash> FlashReference<'a, F> {
+    /// Constructs a new flash reference to the image of the given length at the given offset.
+    pub fn new(flash: &'a mut F, offset: u32, len: u32) -> Self {
+        assert!(F::READ_SIZE == 1);
+
+        Self { flash, offset, len }
+    }
+}
+
+impl<F: ReadNorFlash> ReadReference for FlashReference<'_, F> {
+    type Error = F::Error;
+
+    fn read(&mut self, offset: u64, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let len = (self.len as u64)
+            .saturating_sub(offset)
+            .min(buffer.len() as u64) as usize;
+
+        if len != 0 {
+            self.flash
+                .read(self.offset + offset as u32, &mut buffer[..len])?;
+        }
+
+        buffer[len..].fill(0);
+
+        Ok(())
+    }
+}
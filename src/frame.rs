@@ -0,0 +1,191 @@
+use crate::{
+    Code, DecodeSliceError, Decoder, DecoderState, DefaultCode, EncodeSliceError, Encoder,
+    EncoderState, ExtendedDecoder,
+};
+
+/// Number of bytes filtered at a time on the stack by the frame encoder.
+const CHUNK_LEN: usize = 64;
+
+/// Streaming encoder context applying a reversible pre-filter to data made of frames of `N` bytes,
+/// such as FPGA configuration frames or raster bitmap rows, which replaces each frame with its
+/// bitwise XOR with the previous frame before encoding it.
+///
+/// Frames which are similar to the previous frame become sparse, so they compress much better.
+/// The encoded data must be decoded by a [`FrameDecoder`] with the same frame length.
+#[derive(Debug)]
+pub struct FrameEncoder<const N: usize, C = DefaultCode> {
+    encoder: Encoder<C>,
+    previous: [u8; N],
+    offset: usize,
+    queued_term: bool,
+    queued_flush: bool,
+}
+
+impl<const N: usize, C: Code> FrameEncoder<N, C> {
+    /// Constructs a new frame encoder instance in its initial state, wrapping the given encoder.
+    pub const fn new(encoder: Encoder<C>) -> Self {
+        assert!(N != 0);
+
+        Self {
+            encoder,
+            previous: [0; N],
+            offset: 0,
+            queued_term: false,
+            queued_flush: false,
+        }
+    }
+
+    /// Steps this frame encoder instance like [`Encoder::step`], returning a `(bytes consumed,
+    /// bytes produced, state)` tuple.
+    pub fn step(&mut self, consumed: &[u8], produced: &mut [u8]) -> (usize, usize, EncoderState) {
+        let mut consumed_len = 0;
+        let mut produced_len = 0;
+
+        let mut buffer = [0u8; CHUNK_LEN];
+
+        loop {
+            let remaining = &consumed[consumed_len..];
+            let amount = remaining.len().min(N - self.offset).min(CHUNK_LEN);
+
+            let filtered = &mut buffer[..amount];
+            let previous = &mut self.previous[self.offset..][..amount];
+
+            for ((byte, &next), &previous) in filtered.iter_mut().zip(remaining).zip(&*previous) {
+                *byte = next ^ previous;
+            }
+
+            if amount == remaining.len() {
+                if self.queued_flush {
+                    self.queued_flush = false;
+                    self.encoder.flush();
+                }
+
+                if self.queued_term {
+                    self.encoder.set_consumed_bytes_end();
+                }
+            }
+
+            let (len, produced_now, state) =
+                self.encoder.step(filtered, &mut produced[produced_len..]);
+
+            previous[..len].copy_from_slice(&remaining[..len]);
+            self.offset = (self.offset + len) % N;
+
+            consumed_len += len;
+            produced_len += produced_now;
+
+            if state != EncoderState::CanConsume || consumed_len == consumed.len() {
+                return (consumed_len, produced_len, state);
+            }
+        }
+    }
+
+    /// Requests the encoder to flush all input consumed by the time it runs out of input bytes,
+    /// like [`Encoder::flush`].
+    pub fn flush(&mut self) {
+        self.queued_flush = true;
+    }
+
+    /// Informs the encoder that no further input bytes are available.
+    pub fn set_consumed_bytes_end(&mut self) {
+        self.queued_term = true;
+    }
+}
+
+/// Decoder wrapped by a [`FrameDecoder`], depending on the format of the encoded data.
+#[derive(Debug)]
+enum FrameInner<C> {
+    Plain(Decoder<C>),
+    Extended(ExtendedDecoder<C>),
+}
+
+/// Streaming decoder context for data encoded by a [`FrameEncoder`] with frames of `N` bytes,
+/// undoing its pre-filter using the previously decoded frame.
+#[derive(Debug)]
+pub struct FrameDecoder<const N: usize, C = DefaultCode> {
+    decoder: FrameInner<C>,
+    previous: [u8; N],
+    offset: usize,
+}
+
+impl<const N: usize, C: Code> FrameDecoder<N, C> {
+    /// Constructs a new frame decoder instance in its initial state, wrapping the given decoder.
+    pub const fn new(decoder: Decoder<C>) -> Self {
+        Self::with_inner(FrameInner::Plain(decoder))
+    }
+
+    /// Constructs a new frame decoder instance in its initial state, wrapping the given decoder
+    /// for data encoded in [`Format::V2`](crate::Format::V2).
+    pub const fn with_extended(decoder: ExtendedDecoder<C>) -> Self {
+        Self::with_inner(FrameInner::Extended(decoder))
+    }
+
+    const fn with_inner(decoder: FrameInner<C>) -> Self {
+        assert!(N != 0);
+
+        Self {
+            decoder,
+            previous: [0; N],
+            offset: 0,
+        }
+    }
+
+    /// Steps this frame decoder instance like [`Decoder::step`], returning a `(bytes consumed,
+    /// bytes produced, state)` tuple.
+    pub fn step(&mut self, consumed: &[u8], produced: &mut [u8]) -> (usize, usize, DecoderState) {
+        let (consumed_len, produced_len, state) = match &mut self.decoder {
+            FrameInner::Plain(decoder) => decoder.step(consumed, produced),
+            FrameInner::Extended(decoder) => decoder.step(consumed, produced),
+        };
+
+        for byte in &mut produced[..produced_len] {
+            *byte ^= self.previous[self.offset];
+
+            self.previous[self.offset] = *byte;
+            self.offset = (self.offset + 1) % N;
+        }
+
+        (consumed_len, produced_len, state)
+    }
+}
+
+/// Convenient helper function to directly encode data made of frames of `N` bytes into a
+/// destination byte slice using the pre-filter of [`FrameEncoder`].
+pub fn encode_frames_into_slice<const N: usize>(
+    input: &[u8],
+    output: &mut [u8],
+) -> Result<usize, EncodeSliceError> {
+    let mut encoder = FrameEncoder::<N>::new(Encoder::new());
+    encoder.set_consumed_bytes_end();
+
+    let (_, produced_len, state) = encoder.step(input, output);
+
+    match state {
+        EncoderState::CanConsume => unreachable!("is given entire input"),
+        EncoderState::CanProduce => Err(EncodeSliceError::NeedsMoreSpace),
+        EncoderState::Terminated => Ok(produced_len),
+    }
+}
+
+/// Convenient helper function to directly decode data encoded by [`encode_frames_into_slice`]
+/// with the same frame length into a destination byte slice.
+pub fn decode_frames_from_slice<const N: usize>(
+    input: &[u8],
+    output: &mut [u8],
+) -> Result<usize, DecodeSliceError> {
+    let mut decoder = FrameDecoder::<N>::new(Decoder::new());
+
+    let (_, produced_len, state) = decoder.step(input, output);
+
+    match state {
+        DecoderState::CanConsume => Err(DecodeSliceError::TruncatedInput),
+        DecoderState::CanProduce => Err(DecodeSliceError::NeedsMoreSpace),
+        DecoderState::Terminated {
+            corrupted: true, ..
+        } => Err(DecodeSliceError::Corrupted),
+        DecoderState::Terminated {
+            unaligned: true, ..
+        } => Err(DecodeSliceError::Unaligned),
+        DecoderState::Terminated { .. } => Ok(produced_len),
+    }
+}
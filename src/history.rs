@@ -0,0 +1,70 @@
+/// Number of bits used to represent the distance of a back-reference.
+pub(crate) const HISTORY_BITS: usize = 4;
+
+/// Number of runs remembered for back-references.
+pub(crate) const HISTORY_LEN: usize = 1 << HISTORY_BITS;
+
+/// Minimum number of runs repeated for the encoder to represent them as a back-reference.
+pub(crate) const MIN_MATCH_RUNS: usize = 8;
+
+/// Maximum number of runs represented by a single back-reference produced by the encoder, such
+/// that its fields fit in 32 bits.
+pub(crate) const MAX_MATCH_RUNS: usize = 511;
+
+/// Progress of the decoder through the fields following a mode change symbol when back-references
+/// are enabled.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum BackrefPhase {
+    /// No mode change symbol is in progress.
+    Idle,
+    /// The bit distinguishing a plain mode change from a back-reference is being decoded.
+    Kind,
+    /// The mode and distance of a back-reference are being decoded.
+    Head,
+    /// The number of runs repeated by a back-reference is being decoded.
+    Count,
+}
+
+/// Ring buffer of the most recent runs represented by run symbols or back-references, as used by
+/// back-references.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RunHistory {
+    lens: [usize; HISTORY_LEN],
+    bits: u16,
+    pos: u8,
+    len: u8,
+}
+
+impl RunHistory {
+    /// Constructs an empty history.
+    pub const fn new() -> Self {
+        Self {
+            lens: [0; HISTORY_LEN],
+            bits: 0,
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// Appends a run of the given bit and length, evicting the oldest run if the history is full.
+    pub fn push(&mut self, bit: bool, len: usize) {
+        let pos = self.pos as usize;
+
+        self.lens[pos] = len;
+        self.bits = (self.bits & !(1 << pos)) | ((bit as u16) << pos);
+        self.pos = ((pos + 1) % HISTORY_LEN) as u8;
+        self.len = (self.len + 1).min(HISTORY_LEN as u8);
+    }
+
+    /// Returns the `(bit, length)` of the run the given number of runs back, starting from one for
+    /// the most recent run, or `None` if there is no such run.
+    pub fn get(&self, distance: usize) -> Option<(bool, usize)> {
+        if distance == 0 || distance > self.len as usize {
+            return None;
+        }
+
+        let pos = (self.pos as usize + HISTORY_LEN - distance) % HISTORY_LEN;
+
+        Some((self.bits & (1 << pos) != 0, self.lens[pos]))
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{DecodeSliceError, Decoder, DecoderState};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal::spi::SpiBus;
+
+/// Errors that may occur while configuring an iCE40 FPGA.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Ice40Error<S, P> {
+    /// The SPI bus reported an error.
+    Spi(S),
+    /// One of the control pins reported an error.
+    Pin(P),
+    /// The encoded bitstream could not be decoded.
+    Decode(DecodeSliceError),
+    /// The FPGA did not assert CDONE after receiving the bitstream.
+    NotDone,
+}
+
+/// Driver configuring an iCE40 FPGA in SPI peripheral mode from an encoded bitstream.
+///
+/// The SPI bus must be configured by the caller (mode 3, MSB first, at most 25 MHz) and the chip
+/// select line is driven separately so that the dummy clocks required by the configuration
+/// sequence can be issued with chip select deasserted. Bitstreams are decoded in chunks of `N`
+/// bytes on the stack, so larger values of `N` trade memory for fewer, longer SPI transfers.
+#[derive(Debug)]
+pub struct Ice40<SPI, CS, CRESET, CDONE, D> {
+    spi: SPI,
+    cs: CS,
+    creset: CRESET,
+    cdone: CDONE,
+    delay: D,
+
+    decoder: Decoder,
+}
+
+impl<SPI, CS, CRESET, CDONE, D, P> Ice40<SPI, CS, CRESET, CDONE, D>
+where
+    SPI: SpiBus,
+    CS: OutputPin<Error = P>,
+    CRESET: OutputPin<Error = P>,
+    CDONE: InputPin<Error = P>,
+    D: DelayNs,
+{
+    /// Constructs a new driver from the SPI bus, control pins and a delay provider.
+    pub fn new(spi: SPI, cs: CS, creset: CRESET, cdone: CDONE, delay: D) -> Self {
+        Self {
+            spi,
+            cs,
+            creset,
+            cdone,
+            delay,
+
+            decoder: Decoder::new(),
+        }
+    }
+
+    /// Releases the SPI bus, control pins and delay provider held by this driver.
+    pub fn release(self) -> (SPI, CS, CRESET, CDONE, D) {
+        (self.spi, self.cs, self.creset, self.cdone, self.delay)
+    }
+
+    /// Resets the FPGA into SPI peripheral configuration mode, ready to receive a bitstream.
+    pub fn begin(&mut self) -> Result<(), Ice40Error<SPI::Error, P>> {
+        self.decoder.reset();
+
+        self.cs.set_low().map_err(Ice40Error::Pin)?;
+        self.creset.set_low().map_err(Ice40Error::Pin)?;
+        self.delay.delay_us(1);
+        self.creset.set_high().map_err(Ice40Error::Pin)?;
+        self.delay.delay_us(1200);
+
+        self.cs.set_high().map_err(Ice40Error::Pin)?;
+        self.dummy_clocks(1)?;
+        self.cs.set_low().map_err(Ice40Error::Pin)
+    }
+
+    /// Decodes the given bytes of the encoded bitstream and sends them to the FPGA, returning
+    /// `true` once the encoded bitstream has terminated or `false` if more bytes are required.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is zero.
+    pub fn write<const N: usize>(
+        &mut self,
+        mut consumed: &[u8],
+    ) -> Result<bool, Ice40Error<SPI::Error, P>> {
+        assert!(N != 0);
+
+        let mut buffer = [0u8; N];
+
+        loop {
+            let (consumed_len, produced_len, state) = self.decoder.step(consumed, &mut buffer);
+
+            consumed = &consumed[consumed_len..];
+
+            if produced_len != 0 {
+                let produced = &buffer[..produced_len];
+                self.spi.write(produced).map_err(Ice40Error::Spi)?;
+            }
+
+            match state {
+                DecoderState::CanConsume => return Ok(false),
+                DecoderState::CanProduce => continue,
+                DecoderState::Terminated {
+                    corrupted: true, ..
+                } => return Err(Ice40Error::Decode(DecodeSliceError::Corrupted)),
+                DecoderState::Terminated {
+                    unaligned: true, ..
+                } => return Err(Ice40Error::Decode(DecodeSliceError::Unaligned)),
+                DecoderState::Terminated { .. } => return Ok(true),
+            }
+        }
+    }
+
+    /// Completes the configuration sequence once the entire bitstream has been written, issuing
+    /// the dummy clocks needed for the FPGA to start up and checking that it asserted CDONE.
+    pub fn finish(&mut self) -> Result<(), Ice40Error<SPI::Error, P>> {
+        self.spi.flush().map_err(Ice40Error::Spi)?;
+        self.cs.set_high().map_err(Ice40Error::Pin)?;
+
+        self.dummy_clocks(13)?;
+
+        if !self.cdone.is_high().map_err(Ice40Error::Pin)? {
+            return Err(Ice40Error::NotDone);
+        }
+
+        self.dummy_clocks(7)
+    }
+
+    /// Configures the FPGA with the given encoded bitstream.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is zero.
+    pub fn configure<const N: usize>(
+        &mut self,
+        input: &[u8],
+    ) -> Result<(), Ice40Error<SPI::Error, P>> {
+        self.begin()?;
+
+        if !self.write::<N>(input)? {
+            return Err(Ice40Error::Decode(DecodeSliceError::TruncatedInput));
+        }
+
+        self.finish()
+    }
+
+    fn dummy_clocks(&mut self, bytes: usize) -> Result<(), Ice40Error<SPI::Error, P>> {
+        for _ in 0..bytes {
+            self.spi.write(&[0x00]).map_err(Ice40Error::Spi)?;
+        }
+
+        self.spi.flush().map_err(Ice40Error::Spi)
+    }
+}
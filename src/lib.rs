@@ -4,6 +4,77 @@
 #![forbid(unsafe_code)]
 #![forbid(missing_docs)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+mod bitmap;
+
+mod code;
+mod compare;
+mod decode;
+mod delta;
+mod extended;
+mod extent;
+
+#[cfg(feature = "embedded-storage")]
+mod flash;
+
+mod frame;
+
+#[cfg(feature = "embedded-hal")]
+mod ice40;
+
+mod history;
+mod logic;
+mod pattern;
+mod positions;
+mod range;
+mod rank;
+mod rice;
+mod runs;
+mod splice;
+mod symbol;
+
+#[cfg(feature = "alloc")]
+mod train;
+
+mod verify;
+
+use code::Tables;
+use core::marker::PhantomData;
+use decode::{RunDecoder, SymbolDecoder};
+use history::{RunHistory, HISTORY_BITS, HISTORY_LEN, MAX_MATCH_RUNS, MIN_MATCH_RUNS};
+use pattern::{PatternPhase, LOOKAHEAD_LEN};
+use rice::RiceParams;
+use runs::{Run, RunPairs, RunWriter, Runs};
+use symbol::{Symbol, SymbolReader};
+
+pub use code::*;
+pub use compare::*;
+pub use delta::*;
+pub use extended::*;
+pub use extent::*;
+pub use frame::*;
+pub use logic::*;
+pub use positions::*;
+pub use range::*;
+pub use rank::*;
+pub use splice::*;
+pub use verify::*;
+
+#[cfg(feature = "alloc")]
+pub use bitmap::*;
+
+#[cfg(feature = "alloc")]
+pub use train::*;
+
+#[cfg(feature = "embedded-storage")]
+pub use flash::*;
+
+#[cfg(feature = "embedded-hal")]
+pub use ice40::*;
+
 /// Possible states the decoder can be in.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum DecoderState {
@@ -20,199 +91,165 @@ pub enum DecoderState {
     },
 }
 
-/// Streaming decoder context.
-#[derive(Debug)]
-pub struct Decoder {
-    symbol_bits: usize,
-    queued_bits: usize,
-    output_bits: usize,
-
-    symbol_data: u32,
-    output_data: u8,
+/// Revisions of the encoded representation, as described in the README.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Format {
+    /// The original representation, in which long runs are split into continuated symbols.
+    #[default]
+    V1,
+    /// The revised representation beginning with a header, in which long runs are represented by
+    /// a single extended symbol. It is decoded by an [`ExtendedDecoder`].
+    V2,
+}
 
-    queued_mode: bool,
-    symbol_mode: bool,
-    symbol_term: bool,
+/// Streaming decoder context, decoding symbols from the code `C`.
+///
+/// Only [`Format::V1`] is decoded, which keeps the state of this decoder minimal. Encoded data in
+/// [`Format::V2`] is decoded by an [`ExtendedDecoder`] instead.
+#[derive(Debug)]
+pub struct Decoder<C = DefaultCode> {
+    inner: RunDecoder<PlainSymbols<C>, bool>,
 }
 
 impl Decoder {
     /// Constructs a new decoder instance in its initial state.
     pub const fn new() -> Self {
-        Self {
-            symbol_bits: 0,
-            queued_bits: 0,
-            output_bits: 0,
-
-            symbol_data: 0,
-            output_data: 0,
+        Self::with_code()
+    }
+}
 
-            queued_mode: false,
-            symbol_mode: false,
-            symbol_term: false,
+impl<C: Code> Decoder<C> {
+    /// Constructs a new decoder instance in its initial state, decoding symbols from the code `C`
+    /// instead of the default code.
+    pub const fn with_code() -> Self {
+        Self {
+            inner: RunDecoder::new(PlainSymbols::new(), false),
         }
     }
 
     /// Resets this decoder instance to its initial state.
     pub fn reset(&mut self) {
-        *self = Self::new();
+        *self = Self::with_code();
     }
 
     /// Steps this decoder instance, returning a `(bytes consumed, bytes produced, state)` tuple.
     pub fn step(&mut self, consumed: &[u8], produced: &mut [u8]) -> (usize, usize, DecoderState) {
-        let mut consumed_len = 0;
-        let mut produced_len = 0;
-
-        loop {
-            if self.consume(consumed, &mut consumed_len) {
-                return (consumed_len, produced_len, DecoderState::CanConsume);
-            }
-
-            if self.produce(produced, &mut produced_len) {
-                return (consumed_len, produced_len, DecoderState::CanProduce);
-            }
-
-            if self.symbol_term {
-                break;
-            }
-        }
+        self.inner.step(consumed, produced)
+    }
 
-        debug_assert!(self.symbol_bits <= 7);
-        debug_assert!(self.queued_bits == 0);
+    /// Steps this decoder instance like [`Decoder::step`], except that runs covering whole output
+    /// bytes are reported as [`Extent::Fill`] instead of being written into the produced slice,
+    /// returning a `(bytes consumed, extent, state)` tuple.
+    ///
+    /// Decoded bytes are reported as [`Extent::Data`], possibly empty if no bytes were produced.
+    pub fn step_extent<'a>(
+        &mut self,
+        consumed: &[u8],
+        produced: &'a mut [u8],
+    ) -> (usize, Extent<'a>, DecoderState) {
+        self.inner.step_extent(consumed, produced)
+    }
 
-        (
-            consumed_len,
-            produced_len,
-            DecoderState::Terminated {
-                corrupted: self.symbol_data != 0,
-                unaligned: self.output_bits != 0,
-            },
-        )
+    /// Skips up to the given number of decoded bits without producing them, returning a
+    /// `(bytes consumed, bits skipped, state)` tuple.
+    ///
+    /// Fewer bits than requested are skipped only if more input is required or if the decoded
+    /// data terminates first. Decoded bits following the skipped ones are produced by subsequent
+    /// steps as if they started at the beginning of the output, so skipping a number of bits that
+    /// is not a multiple of eight shifts the alignment of the output bytes.
+    pub fn skip(&mut self, consumed: &[u8], bits: u64) -> (usize, u64, DecoderState) {
+        self.inner.skip(consumed, bits)
     }
 
     /// Retrieves the (right-aligned) last partial output byte.
     pub fn partial_output_byte(&self) -> Option<(u8, usize)> {
-        if self.symbol_term && self.output_bits != 0 {
-            Some((self.output_data, self.output_bits))
-        } else {
-            None
-        }
+        self.inner.partial_output_byte()
     }
+}
 
-    fn consume(&mut self, consumed: &[u8], consumed_len: &mut usize) -> bool {
-        if self.queued_bits == 0 && !self.symbol_term {
-            while self.symbol_bits < 24 {
-                let Some(&input_byte) = consumed.get(*consumed_len) else {
-                    return true;
-                };
-
-                self.symbol_data |= (input_byte as u32) << (24 - self.symbol_bits);
-                self.symbol_bits += 8;
-                *consumed_len += 1;
-            }
-
-            let shift_out;
-            let mut count: usize = 0;
-            let mut cont = false;
-
-            let prefix_len = self.symbol_data.leading_zeros() as usize;
-
-            if prefix_len < 12 {
-                if self.symbol_mode {
-                    shift_out = prefix_len + 1;
-                    count = shift_out;
-                } else {
-                    shift_out = 2 * (prefix_len + 1);
-                    let mask = (1 << (prefix_len + 1)) - 1;
-
-                    let payload = (self.symbol_data >> (32 - shift_out)) as u16;
-
-                    count = ((payload & mask) + mask) as usize;
-                }
-            } else {
-                shift_out = 24;
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-                let payload = ((self.symbol_data >> 8) as u16) & 0b1111_1111_1111;
+/// Symbol decoder of a [`Decoder`], decoding the symbols of [`Format::V1`].
+#[derive(Debug)]
+struct PlainSymbols<C> {
+    symbol_bits: usize,
+    symbol_data: u32,
 
-                if payload == 0xFFF {
-                    self.symbol_term = true;
-                } else if payload != 0xFFE {
-                    if self.symbol_mode {
-                        count = (payload + 13) as usize;
-                    } else {
-                        count = (payload + 8191) as usize;
-                    }
+    symbol_mode: bool,
+    symbol_term: bool,
 
-                    if payload == 0xFFD {
-                        cont = true;
-                    }
-                }
-            }
+    code: PhantomData<C>,
+}
 
-            self.symbol_bits -= shift_out;
-            self.symbol_data <<= shift_out;
+impl<C> PlainSymbols<C> {
+    const fn new() -> Self {
+        Self {
+            symbol_bits: 0,
+            symbol_data: 0,
 
-            if count > 0 {
-                self.queued_bits = count;
-                self.queued_mode = self.symbol_mode;
-            }
+            symbol_mode: false,
+            symbol_term: false,
 
-            if !cont {
-                self.symbol_mode = !self.symbol_mode;
-            }
+            code: PhantomData,
         }
-
-        false
     }
+}
 
-    fn produce(&mut self, produced: &mut [u8], produced_len: &mut usize) -> bool {
-        if self.output_bits == 0 && self.queued_bits >= 8 {
-            let transfer = (self.queued_bits / 8).min(produced.len() - *produced_len);
-
-            if transfer == 0 {
+impl<C: Code> SymbolDecoder<bool> for PlainSymbols<C> {
+    fn decode(
+        &mut self,
+        consumed: &[u8],
+        consumed_len: &mut usize,
+        queued_bits: &mut usize,
+        queued_mode: &mut bool,
+    ) -> bool {
+        while self.symbol_bits < 24 {
+            let Some(&input_byte) = consumed.get(*consumed_len) else {
                 return true;
-            }
+            };
 
-            let slice = &mut produced[*produced_len..][..transfer];
+            self.symbol_data |= (input_byte as u32) << (24 - self.symbol_bits);
+            self.symbol_bits += 8;
+            *consumed_len += 1;
+        }
 
-            if self.queued_mode {
-                slice.fill(0xFF);
-            } else {
-                slice.fill(0x00);
-            }
+        let symbol = Symbol::decode::<C>(self.symbol_data, self.symbol_mode);
 
-            self.queued_bits -= transfer * 8;
-            *produced_len += transfer;
-        } else if self.output_bits != 8 && self.queued_bits != 0 {
-            let amount = (8 - self.output_bits).min(self.queued_bits);
+        self.symbol_bits -= symbol.len;
+        self.symbol_data <<= symbol.len;
 
-            let mut word = 0;
+        if symbol.term {
+            self.symbol_term = true;
+        }
 
-            if self.queued_mode {
-                word = (1 << amount) - 1;
-            }
+        if symbol.count > 0 {
+            *queued_bits = symbol.count;
+            *queued_mode = self.symbol_mode;
+        }
 
-            self.output_data <<= amount;
-            self.output_data |= word;
-            self.output_bits += amount;
-            self.queued_bits -= amount;
-        } else if self.output_bits == 8 {
-            if let Some(byte) = produced.get_mut(*produced_len) {
-                *byte = self.output_data;
-                self.output_data = 0;
-                self.output_bits = 0;
-                *produced_len += 1;
-            } else {
-                return true;
-            }
+        if !symbol.cont {
+            self.symbol_mode = !self.symbol_mode;
         }
 
         false
     }
-}
 
-impl Default for Decoder {
-    fn default() -> Self {
-        Self::new()
+    fn is_terminated(&self) -> bool {
+        self.symbol_term
+    }
+
+    fn is_corrupted(&self) -> bool {
+        debug_assert!(self.symbol_bits <= 7);
+
+        self.symbol_data != 0
+    }
+
+    fn next_mode(&self) -> Option<bool> {
+        Some(self.symbol_mode)
     }
 }
 
@@ -227,45 +264,219 @@ pub enum EncoderState {
     Terminated,
 }
 
-/// Streaming encoder context.
+/// Streaming encoder context, encoding symbols from the code `C`.
 #[derive(Debug)]
-pub struct Encoder {
+pub struct Encoder<C = DefaultCode> {
     symbol_bits: usize,
     queued_bits: usize,
     output_bits: usize,
+    partial_bits: usize,
+    symbol_flush: usize,
+    ext_bits: usize,
+    pattern_count: usize,
+    match_distance: usize,
+    match_len: usize,
+    replay_len: usize,
 
     symbol_data: u32,
+    ext_data: usize,
     output_data: u8,
+    partial_data: u8,
+    pattern_data: u32,
+    lookahead: [u8; LOOKAHEAD_LEN],
+    lookahead_len: usize,
+
+    format: Format,
+    rice: RiceParams,
+    pattern: PatternPhase,
+    history: RunHistory,
 
+    symbol_head: bool,
     queued_done: bool,
     queued_mode: bool,
+    queued_flush: bool,
     symbol_term: bool,
     queued_term: bool,
     output_term: bool,
+    adaptive: bool,
+    patterns: bool,
+    pattern_word: bool,
+    pattern_mode: bool,
+    backrefs: bool,
+
+    code: PhantomData<C>,
 }
 
 impl Encoder {
     /// Constructs a new encoder instance in its initial state.
     pub const fn new() -> Self {
+        Self::with_format(Format::V1)
+    }
+
+    /// Constructs a new encoder instance in its initial state, encoding into the given format.
+    pub const fn with_format(format: Format) -> Self {
+        Self::with_code(format)
+    }
+
+    /// Constructs an encoder instance resuming from the given encoded data, as if its decoded data
+    /// had been consumed by a new encoder, returning the encoder along with the number of leading
+    /// bytes of the encoded data which are to be kept.
+    ///
+    /// The bytes produced by the returned encoder are to be written immediately after the kept
+    /// bytes, replacing the remainder of the encoded data. When resuming from encoded data which
+    /// was produced by an encoder without flushing, the result is identical to encoding all of the
+    /// data at once.
+    pub fn resume_from(encoded: &[u8]) -> Result<(Self, usize), DecodeSliceError> {
+        let mut reader = SymbolReader::new(encoded);
+
+        let mut run: Option<(u64, bool, usize)> = None;
+        let mut chain: Option<(u64, bool, usize)> = None;
+
+        loop {
+            let (bit_pos, mode) = (reader.bit_pos(), reader.mode());
+            let symbol = reader.next_symbol()?;
+
+            if !symbol.term {
+                let (_, _, count) = chain.get_or_insert((bit_pos, mode, 0));
+                *count += symbol.count;
+
+                if symbol.cont {
+                    continue;
+                }
+            }
+
+            match (chain.take(), &mut run) {
+                (Some((_, _, 0)), _) | (None, _) => {}
+                (Some((_, mode, count)), Some((_, run_mode, run_count))) if mode == *run_mode => {
+                    *run_count += count;
+                }
+                (Some(chain), _) => run = Some(chain),
+            }
+
+            if symbol.term {
+                break;
+            }
+        }
+
+        if !reader.is_padded() {
+            return Err(DecodeSliceError::Corrupted);
+        }
+
+        let (bit_pos, mode, count) = run.unwrap_or((0, false, 0));
+
+        let kept_len = (bit_pos / 8) as usize;
+        let kept_bits = (bit_pos % 8) as usize;
+
+        let mut encoder = Self::new();
+
+        if kept_bits != 0 {
+            encoder.symbol_data =
+                ((encoded[kept_len] >> (8 - kept_bits)) as u32) << (32 - kept_bits);
+            encoder.symbol_bits = kept_bits;
+        }
+
+        encoder.queued_mode = mode;
+        encoder.queued_bits = count;
+
+        Ok((encoder, kept_len))
+    }
+}
+
+impl<C: Code> Encoder<C> {
+    /// Constructs a new encoder instance in its initial state, encoding into the given format
+    /// using the code `C` instead of the default code.
+    pub const fn with_code(format: Format) -> Self {
         Self {
             symbol_bits: 0,
             queued_bits: 0,
             output_bits: 0,
+            partial_bits: 0,
+            symbol_flush: 0,
+            ext_bits: 0,
+            pattern_count: 0,
+            match_distance: 0,
+            match_len: 0,
+            replay_len: 0,
 
             symbol_data: 0,
+            ext_data: 0,
             output_data: 0,
+            partial_data: 0,
+            pattern_data: 0,
+            lookahead: [0; LOOKAHEAD_LEN],
+            lookahead_len: 0,
+
+            format,
+            rice: RiceParams::new(),
+            pattern: PatternPhase::Idle,
+            history: RunHistory::new(),
 
+            symbol_head: matches!(format, Format::V2),
             queued_done: false,
             queued_mode: false,
+            queued_flush: false,
             symbol_term: false,
             queued_term: false,
             output_term: false,
+            adaptive: false,
+            patterns: false,
+            pattern_word: false,
+            pattern_mode: false,
+            backrefs: false,
+
+            code: PhantomData,
         }
     }
 
-    /// Resets this encoder instance to its initial state.
+    /// Resets this encoder instance to its initial state, keeping its format and whether it uses
+    /// adaptive coding, pattern symbols and back-references.
     pub fn reset(&mut self) {
-        *self = Self::new();
+        let (adaptive, patterns, backrefs) = (self.adaptive, self.patterns, self.backrefs);
+
+        *self = Self::with_code(self.format);
+        self.adaptive = adaptive;
+        self.patterns = patterns;
+        self.backrefs = backrefs;
+    }
+
+    /// Requests the encoder to represent run lengths using adaptive Golomb-Rice codes instead of
+    /// the code tables, as described in the README. This is flagged in the header of the encoded
+    /// data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the format is not [`Format::V2`] or if the header has already been produced.
+    pub fn set_adaptive(&mut self) {
+        assert!(self.format == Format::V2 && self.symbol_head);
+
+        self.adaptive = true;
+    }
+
+    /// Requests the encoder to represent repetitions of a byte other than `0x00` and `0xFF` or of a
+    /// 32-bit word using pattern symbols, as described in the README.
+    ///
+    /// Patterns are detected at input byte boundaries by buffering up to 32 input bytes, so the
+    /// encoded data does not depend on how the input is split into steps.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the format is not [`Format::V2`] or if the header has already been produced.
+    pub fn set_patterns(&mut self) {
+        assert!(self.format == Format::V2 && self.symbol_head);
+
+        self.patterns = true;
+    }
+
+    /// Requests the encoder to represent sequences of runs repeating one of the last 16 runs using
+    /// back-references, as described in the README.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the format is not [`Format::V2`] or if the header has already been produced.
+    pub fn set_backrefs(&mut self) {
+        assert!(self.format == Format::V2 && self.symbol_head);
+
+        self.backrefs = true;
     }
 
     /// Steps this encoder instance, returning a `(bytes consumed, bytes produced, state)` tuple.
@@ -282,7 +493,7 @@ impl Encoder {
                 return (consumed_len, produced_len, EncoderState::CanProduce);
             }
 
-            if self.output_term {
+            if self.output_term && self.symbol_bits == 0 {
                 break;
             }
         }
@@ -294,19 +505,184 @@ impl Encoder {
         (consumed_len, produced_len, EncoderState::Terminated)
     }
 
+    /// Steps this encoder instance like [`Encoder::step`] with up to `count` zero input bytes,
+    /// returning a `(zero bytes consumed, bytes produced, state)` tuple.
+    ///
+    /// Zero bytes continuing a run of zero bits are consumed in constant time.
+    pub fn step_zeroes(&mut self, count: u64, produced: &mut [u8]) -> (u64, usize, EncoderState) {
+        let mut consumed_len = 0;
+        let mut produced_len = 0;
+
+        loop {
+            if self.output_bits == 0
+                && !self.queued_mode
+                && !self.queued_done
+                && !self.symbol_term
+                && self.symbol_flush == 0
+                && self.pattern == PatternPhase::Idle
+                && self.lookahead[..self.lookahead_len]
+                    .iter()
+                    .all(|&byte| byte == 0)
+            {
+                let capacity = ((usize::MAX - self.queued_bits) / 8) as u64;
+                let transfer = (count - consumed_len).min(capacity);
+
+                self.queued_bits += transfer as usize * 8;
+                consumed_len += transfer;
+            }
+
+            let zeroes: &[u8] = if consumed_len < count { &[0] } else { &[] };
+            let mut zeroes_len = 0;
+
+            let needs_input = self.consume(zeroes, &mut zeroes_len);
+            consumed_len += zeroes_len as u64;
+
+            if needs_input {
+                if consumed_len < count {
+                    continue;
+                }
+
+                return (consumed_len, produced_len, EncoderState::CanConsume);
+            }
+
+            if self.produce(produced, &mut produced_len) {
+                return (consumed_len, produced_len, EncoderState::CanProduce);
+            }
+
+            if self.output_term && self.symbol_bits == 0 {
+                break;
+            }
+        }
+
+        (consumed_len, produced_len, EncoderState::Terminated)
+    }
+
+    /// Requests the encoder to flush all input consumed by the time it runs out of input bytes,
+    /// such that the bytes produced up to that point can be decoded to recover all of that input
+    /// (save for any trailing partial output byte) without terminating the encoded data.
+    ///
+    /// The flush is complete once [`Encoder::step`] next returns [`EncoderState::CanConsume`]. It
+    /// is implemented by ending the current run early and appending a pair of mode change symbols,
+    /// so it costs between 48 and 72 bits depending on the input with the code tables. With adaptive
+    /// coding, the mode change symbols are 14 bits long instead of 24, and with back-references,
+    /// each of them is followed by one more bit.
+    pub fn flush(&mut self) {
+        self.queued_flush = true;
+    }
+
     /// Informs the encoder that no further input bytes are available.
     pub fn set_consumed_bytes_end(&mut self) {
         self.queued_term = true;
     }
 
+    /// Informs the encoder that no further input bytes are available after a final (right-aligned)
+    /// partial input byte, of which only the given number of bits are to be encoded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of bits is not less than eight.
+    pub fn set_partial_input_byte(&mut self, byte: u8, bits: usize) {
+        assert!(bits < 8);
+
+        if bits != 0 {
+            self.partial_data = byte << (8 - bits);
+            self.partial_bits = bits;
+        }
+
+        self.queued_term = true;
+    }
+
     fn consume(&mut self, consumed: &[u8], consumed_len: &mut usize) -> bool {
+        if self.symbol_flush != 0 {
+            // Nothing may be consumed until both mode change symbols of the flush are produced,
+            // as a run ending in the meantime would be produced between them.
+            return false;
+        }
+
+        if !self.patterns {
+            return self.consume_bytes(consumed, consumed_len, false);
+        }
+
+        loop {
+            let amount = (LOOKAHEAD_LEN - self.lookahead_len).min(consumed.len() - *consumed_len);
+
+            self.lookahead[self.lookahead_len..][..amount]
+                .copy_from_slice(&consumed[*consumed_len..][..amount]);
+            self.lookahead_len += amount;
+            *consumed_len += amount;
+
+            let lookahead = self.lookahead;
+            let mut lookahead_len = 0;
+            let more = *consumed_len != consumed.len();
+
+            let needs_input =
+                self.consume_bytes(&lookahead[..self.lookahead_len], &mut lookahead_len, more);
+
+            self.lookahead
+                .copy_within(lookahead_len..self.lookahead_len, 0);
+            self.lookahead_len -= lookahead_len;
+
+            if !needs_input || *consumed_len == consumed.len() {
+                return needs_input;
+            }
+        }
+    }
+
+    /// Consumes the given input bytes, which are followed by further input bytes if `more` is set,
+    /// returning whether more input bytes are required.
+    fn consume_bytes(&mut self, consumed: &[u8], consumed_len: &mut usize, more: bool) -> bool {
+        if self.pattern == PatternPhase::Repeat {
+            return self.consume_pattern(consumed, consumed_len, more);
+        }
+
+        if self.pattern != PatternPhase::Idle {
+            return false;
+        }
+
+        if self.patterns && self.output_bits == 0 && !self.queued_done && !self.symbol_term {
+            let remaining = &consumed[*consumed_len..];
+
+            if remaining.len() < LOOKAHEAD_LEN && !self.queued_term && !self.queued_flush {
+                return true;
+            }
+
+            if let Some((data, word, count)) = pattern::detect(remaining) {
+                *consumed_len += count * if word { 4 } else { 1 };
+
+                if self.queued_bits != 0 {
+                    self.queued_mode = !self.queued_mode;
+                    self.queued_done = true;
+                }
+
+                self.pattern = PatternPhase::Repeat;
+                self.pattern_data = data;
+                self.pattern_word = word;
+                self.pattern_count = count;
+                return false;
+            }
+        }
+
         if self.output_bits == 0 && !self.symbol_term {
             if let Some(&byte) = consumed.get(*consumed_len) {
                 self.output_data = byte;
                 self.output_bits = 8;
                 *consumed_len += 1;
+            } else if self.queued_flush {
+                if !self.queued_done {
+                    if self.queued_bits != 0 {
+                        self.queued_mode = !self.queued_mode;
+                        self.queued_done = true;
+                    }
+
+                    self.queued_flush = false;
+                    self.symbol_flush = 2;
+                }
             } else if !self.queued_term {
                 return true;
+            } else if self.partial_bits != 0 {
+                self.output_data = self.partial_data;
+                self.output_bits = self.partial_bits;
+                self.partial_bits = 0;
             }
         }
 
@@ -334,186 +710,170 @@ impl Encoder {
                 self.output_bits -= count;
                 self.queued_bits += count;
             }
-        } else {
+        } else if self.queued_term && self.symbol_flush == 0 {
             self.symbol_term = true;
         }
 
         false
     }
 
+    fn consume_pattern(&mut self, consumed: &[u8], consumed_len: &mut usize, more: bool) -> bool {
+        let len = if self.pattern_word { 4 } else { 1 };
+        let pattern = &self.pattern_data.to_be_bytes()[4 - len..];
+
+        let remaining = &consumed[*consumed_len..];
+        let count = remaining
+            .chunks_exact(len)
+            .take_while(|&next| next == pattern)
+            .count();
+
+        self.pattern_count += count;
+        *consumed_len += count * len;
+
+        let rest = &remaining[count * len..];
+
+        let end = self.queued_term || self.queued_flush;
+
+        if rest.len() < len && pattern.starts_with(rest) && (more || !end) {
+            return true;
+        }
+
+        self.pattern_mode = match remaining.get(count * len) {
+            Some(&byte) => byte & 0x80 != 0,
+            None => self.partial_bits != 0 && self.partial_data & 0x80 != 0,
+        };
+
+        self.pattern = PatternPhase::Symbol;
+
+        false
+    }
+
     fn produce(&mut self, produced: &mut [u8], produced_len: &mut usize) -> bool {
         if self.symbol_bits <= 8 {
-            if self.symbol_term && !self.output_term {
-                self.symbol_data |= 0b000000000000111111111111 << (8 - self.symbol_bits);
+            if self.ext_bits != 0 {
+                let amount = self.ext_bits.min(24);
+                let shift = (self.ext_bits - amount) as u32;
+                let chunk = self.ext_data.checked_shr(shift).unwrap_or(0) & ((1 << amount) - 1);
+
+                self.symbol_data |= (chunk as u32) << (32 - amount - self.symbol_bits);
+                self.symbol_bits += amount;
+                self.ext_bits -= amount;
+            } else if self.symbol_head
+                && (self.symbol_term
+                    || self.queued_done
+                    || self.symbol_flush != 0
+                    || self.pattern == PatternPhase::Symbol)
+            {
+                let mode = self.queued_done && self.queued_mode && self.queued_bits == 0;
+
+                if mode {
+                    self.queued_done = false;
+                }
+
+                if self.adaptive || self.patterns || self.backrefs {
+                    let header = 0b10000
+                        | (mode as u32) << 3
+                        | (self.adaptive as u32) << 2
+                        | (self.patterns as u32) << 1
+                        | self.backrefs as u32;
 
-                if self.symbol_bits == 0 {
-                    self.symbol_bits = 24;
+                    self.symbol_data |= header << (27 - self.symbol_bits);
+                    self.symbol_bits += 5;
                 } else {
-                    self.symbol_bits = 32;
+                    self.symbol_data |= (mode as u32) << (30 - self.symbol_bits);
+                    self.symbol_bits += 2;
                 }
+
+                self.symbol_head = false;
+            } else if self.replay_len != 0 {
+                let (bit, len) = self.history.get(self.replay_len).unwrap();
+
+                self.produce_run(bit, len);
+                self.replay_len -= 1;
+            } else if self.match_len != 0
+                && (self.queued_done
+                    || self.symbol_term
+                    || self.symbol_flush != 0
+                    || self.pattern == PatternPhase::Symbol)
+                && self.match_candidate().is_none()
+            {
+                if self.match_len >= MIN_MATCH_RUNS {
+                    self.produce_mode_change();
+
+                    let mode = if self.queued_done {
+                        !self.queued_mode
+                    } else {
+                        self.queued_mode
+                    };
+
+                    let len_bits = 2 * (usize::BITS - self.match_len.leading_zeros()) as usize - 1;
+
+                    self.ext_data = 1 << (1 + HISTORY_BITS + len_bits)
+                        | (mode as usize) << (HISTORY_BITS + len_bits)
+                        | (self.match_distance - 1) << len_bits
+                        | self.match_len;
+                    self.ext_bits = 2 + HISTORY_BITS + len_bits;
+                } else {
+                    self.replay_len = self.match_len;
+                }
+
+                self.match_len = 0;
+            } else if let Some(distance) = self.match_candidate() {
+                self.history.push(!self.queued_mode, self.queued_bits);
+                self.match_distance = distance;
+                self.match_len += 1;
+
+                self.queued_bits = 0;
+                self.queued_done = false;
+            } else if self.symbol_term && !self.output_term {
+                let len = if self.adaptive {
+                    self.symbol_data |= 0b000000000000001 << (17 - self.symbol_bits);
+                    15
+                } else {
+                    self.symbol_data |= 0b000000000000111111111111 << (8 - self.symbol_bits);
+                    24
+                };
+
+                self.symbol_bits = (self.symbol_bits + len).next_multiple_of(8);
+                self.output_term = true;
             } else if self.queued_done {
-                let mut cont = false;
-
-                if self.queued_mode {
-                    match self.queued_bits {
-                        0 => {
-                            self.symbol_data |=
-                                0b0000_0000_0000_1111_1111_1110 << (8 - self.symbol_bits);
-                            self.symbol_bits += 24;
-                        }
-                        1..=2 => {
-                            self.symbol_data |=
-                                (0b10 | ((self.queued_bits - 1) as u32)) << (30 - self.symbol_bits);
-                            self.symbol_bits += 2;
-                        }
-                        3..=6 => {
-                            self.symbol_data |= (0b0100 | ((self.queued_bits - 3) as u32))
-                                << (28 - self.symbol_bits);
-                            self.symbol_bits += 4;
-                        }
-                        7..=14 => {
-                            self.symbol_data |= (0b001000 | ((self.queued_bits - 7) as u32))
-                                << (26 - self.symbol_bits);
-                            self.symbol_bits += 6;
-                        }
-                        15..=30 => {
-                            self.symbol_data |= (0b00010000 | ((self.queued_bits - 15) as u32))
-                                << (24 - self.symbol_bits);
-                            self.symbol_bits += 8;
-                        }
-                        31..=62 => {
-                            self.symbol_data |= (0b0000100000 | ((self.queued_bits - 31) as u32))
-                                << (22 - self.symbol_bits);
-                            self.symbol_bits += 10;
-                        }
-                        63..=126 => {
-                            self.symbol_data |= (0b000001000000 | ((self.queued_bits - 63) as u32))
-                                << (20 - self.symbol_bits);
-                            self.symbol_bits += 12;
-                        }
-                        127..=254 => {
-                            self.symbol_data |= (0b00000010000000
-                                | ((self.queued_bits - 127) as u32))
-                                << (18 - self.symbol_bits);
-                            self.symbol_bits += 14;
-                        }
-                        255..=510 => {
-                            self.symbol_data |= (0b0000000100000000
-                                | ((self.queued_bits - 255) as u32))
-                                << (16 - self.symbol_bits);
-                            self.symbol_bits += 16;
-                        }
-                        511..=1022 => {
-                            self.symbol_data |= (0b000000001000000000
-                                | ((self.queued_bits - 511) as u32))
-                                << (14 - self.symbol_bits);
-                            self.symbol_bits += 18;
-                        }
-                        1023..=2046 => {
-                            self.symbol_data |= (0b00000000010000000000
-                                | ((self.queued_bits - 1023) as u32))
-                                << (12 - self.symbol_bits);
-                            self.symbol_bits += 20;
-                        }
-                        2047..=4094 => {
-                            self.symbol_data |= (0b0000000000100000000000
-                                | ((self.queued_bits - 2047) as u32))
-                                << (10 - self.symbol_bits);
-                            self.symbol_bits += 22;
-                        }
-                        4095..=8190 => {
-                            self.symbol_data |= (0b000000000001000000000000
-                                | ((self.queued_bits - 4095) as u32))
-                                << (8 - self.symbol_bits);
-                            self.symbol_bits += 24;
-                        }
-                        8191..=12283 => {
-                            self.symbol_data |=
-                                ((self.queued_bits - 8191) as u32) << (8 - self.symbol_bits);
-                            self.symbol_bits += 24;
-                        }
-                        12284.. => {
-                            self.symbol_data |=
-                                0b000000000000111111111101 << (8 - self.symbol_bits);
-                            self.symbol_bits += 24;
-                            self.queued_bits -= 12284;
-                            cont = true;
-                        }
-                    }
+                if let Some(remaining) = self.produce_run(!self.queued_mode, self.queued_bits) {
+                    self.queued_bits = remaining;
                 } else {
-                    match self.queued_bits {
-                        0 => {
-                            self.symbol_data |=
-                                0b0000_0000_0000_1111_1111_1110 << (8 - self.symbol_bits);
-                            self.symbol_bits += 24;
-                        }
-                        1 => {
-                            self.symbol_data |= 0b1 << (31 - self.symbol_bits);
-                            self.symbol_bits += 1;
-                        }
-                        2 => {
-                            self.symbol_data |= 0b01 << (30 - self.symbol_bits);
-                            self.symbol_bits += 2;
-                        }
-                        3 => {
-                            self.symbol_data |= 0b001 << (29 - self.symbol_bits);
-                            self.symbol_bits += 3;
-                        }
-                        4 => {
-                            self.symbol_data |= 0b0001 << (28 - self.symbol_bits);
-                            self.symbol_bits += 4;
-                        }
-                        5 => {
-                            self.symbol_data |= 0b00001 << (27 - self.symbol_bits);
-                            self.symbol_bits += 5;
-                        }
-                        6 => {
-                            self.symbol_data |= 0b000001 << (26 - self.symbol_bits);
-                            self.symbol_bits += 6;
-                        }
-                        7 => {
-                            self.symbol_data |= 0b0000001 << (25 - self.symbol_bits);
-                            self.symbol_bits += 7;
-                        }
-                        8 => {
-                            self.symbol_data |= 0b00000001 << (24 - self.symbol_bits);
-                            self.symbol_bits += 8;
-                        }
-                        9 => {
-                            self.symbol_data |= 0b000000001 << (23 - self.symbol_bits);
-                            self.symbol_bits += 9;
-                        }
-                        10 => {
-                            self.symbol_data |= 0b0000000001 << (22 - self.symbol_bits);
-                            self.symbol_bits += 10;
-                        }
-                        11 => {
-                            self.symbol_data |= 0b00000000001 << (21 - self.symbol_bits);
-                            self.symbol_bits += 11;
-                        }
-                        12 => {
-                            self.symbol_data |= 0b000000000001 << (20 - self.symbol_bits);
-                            self.symbol_bits += 12;
-                        }
-                        13..=4105 => {
-                            self.symbol_data |=
-                                ((self.queued_bits - 13) as u32) << (8 - self.symbol_bits);
-                            self.symbol_bits += 24;
-                        }
-                        4106.. => {
-                            self.symbol_data |=
-                                0b000000000000111111111101 << (8 - self.symbol_bits);
-                            self.symbol_bits += 24;
-                            self.queued_bits -= 4106;
-                            cont = true;
-                        }
+                    if self.backrefs && self.queued_bits != 0 {
+                        self.history.push(!self.queued_mode, self.queued_bits);
                     }
-                }
 
-                if !cont {
                     self.queued_bits = 0;
                     self.queued_done = false;
                 }
+            } else if self.pattern == PatternPhase::Symbol {
+                if self.adaptive {
+                    self.symbol_bits += 15;
+                } else {
+                    self.symbol_data |= 0b000000000000111111111100 << (8 - self.symbol_bits);
+                    self.symbol_bits += 24;
+                }
+
+                self.pattern = PatternPhase::Head;
+            } else if self.pattern == PatternPhase::Head {
+                let head = (self.pattern_mode as u32) << 1 | self.pattern_word as u32;
+
+                self.symbol_data |= head << (30 - self.symbol_bits);
+                self.symbol_bits += 2;
+
+                self.ext_data = self.pattern_data as usize;
+                self.ext_bits = if self.pattern_word { 32 } else { 8 };
+                self.pattern = PatternPhase::Count;
+            } else if self.pattern == PatternPhase::Count {
+                self.ext_data = self.pattern_count;
+                self.ext_bits = 2 * (usize::BITS - self.ext_data.leading_zeros()) as usize - 1;
+
+                self.queued_mode = self.pattern_mode;
+                self.pattern = PatternPhase::Idle;
+            } else if self.symbol_flush != 0 {
+                self.produce_run(false, 0);
+                self.symbol_flush -= 1;
             }
         }
 
@@ -529,11 +889,110 @@ impl Encoder {
             }
         }
 
-        if self.symbol_term && self.symbol_bits == 0 {
-            self.output_term = true;
+        false
+    }
+
+    /// Encodes a symbol representing a run of the given bit and length, returning the remaining
+    /// length of the run if it was a continuated symbol.
+    fn produce_run(&mut self, mode: bool, len: usize) -> Option<usize> {
+        if len == 0 {
+            self.produce_mode_change();
+
+            if self.backrefs {
+                self.ext_data = 0;
+                self.ext_bits = 1;
+            }
+
+            return None;
         }
 
-        false
+        if self.adaptive {
+            let param = self.rice.param(mode);
+            let quotient = (len - 1) >> param;
+
+            if quotient < 12 {
+                let remainder = ((len - 1) & ((1 << param) - 1)) as u32;
+                let symbol_len = quotient + 1 + param;
+
+                self.symbol_data |=
+                    ((1 << param) | remainder) << (32 - symbol_len - self.symbol_bits);
+                self.symbol_bits += symbol_len;
+            } else {
+                self.symbol_data |= 0b0000000000001 << (19 - self.symbol_bits);
+                self.symbol_bits += 13;
+
+                self.ext_data = len - (12 << param);
+                self.ext_bits = 2 * (usize::BITS - self.ext_data.leading_zeros()) as usize - 1;
+            }
+
+            self.rice.update(mode, len);
+
+            return None;
+        }
+
+        let bases = &Tables::<C>::BASES[mode as usize];
+        let limit = bases[12] + 4092;
+
+        if len < bases[12] {
+            let mut index = 0;
+
+            while bases[index + 1] <= len {
+                index += 1;
+            }
+
+            let payload_bits = C::PAYLOAD_BITS[mode as usize][index] as usize;
+            let payload = (len - bases[index]) as u32;
+            let symbol_len = index + 1 + payload_bits;
+
+            self.symbol_data |=
+                ((1 << payload_bits) | payload) << (32 - symbol_len - self.symbol_bits);
+            self.symbol_bits += symbol_len;
+        } else if self.format == Format::V2 && len >= limit {
+            self.symbol_data |= 0b000000000000111111111101 << (8 - self.symbol_bits);
+            self.symbol_bits += 24;
+
+            self.ext_data = len - limit + 1;
+            self.ext_bits = 2 * (usize::BITS - self.ext_data.leading_zeros()) as usize - 1;
+        } else if len <= limit {
+            self.symbol_data |= ((len - bases[12]) as u32) << (8 - self.symbol_bits);
+            self.symbol_bits += 24;
+        } else {
+            self.symbol_data |= 0b000000000000111111111101 << (8 - self.symbol_bits);
+            self.symbol_bits += 24;
+
+            return Some(len - limit - 1);
+        }
+
+        None
+    }
+
+    /// Encodes a mode change symbol, which also begins a back-reference when back-references are
+    /// enabled.
+    fn produce_mode_change(&mut self) {
+        if self.adaptive {
+            self.symbol_data |= 0b00000000000001 << (18 - self.symbol_bits);
+            self.symbol_bits += 14;
+        } else {
+            self.symbol_data |= 0b0000_0000_0000_1111_1111_1110 << (8 - self.symbol_bits);
+            self.symbol_bits += 24;
+        }
+    }
+
+    /// Returns the distance of the back-reference continued or started by the queued run, if any.
+    fn match_candidate(&self) -> Option<usize> {
+        if !self.backrefs || !self.queued_done || self.queued_bits == 0 {
+            return None;
+        }
+
+        let run = Some((!self.queued_mode, self.queued_bits));
+
+        if self.match_len == 0 {
+            (1..=HISTORY_LEN).find(|&distance| self.history.get(distance) == run)
+        } else if self.match_len < MAX_MATCH_RUNS && self.history.get(self.match_distance) == run {
+            Some(self.match_distance)
+        } else {
+            None
+        }
     }
 }
 
@@ -601,5 +1060,46 @@ pub fn encode_into_slice(input: &[u8], output: &mut [u8]) -> Result<usize, Encod
     }
 }
 
+impl From<EncodeSliceError> for DecodeSliceError {
+    fn from(err: EncodeSliceError) -> Self {
+        match err {
+            EncodeSliceError::NeedsMoreSpace => Self::NeedsMoreSpace,
+        }
+    }
+}
+
+/// Convenient helper function to directly encode the first `bit_len` bits (MSB first) of arbitrary
+/// data into a destination byte slice.
+///
+/// # Panics
+///
+/// Panics if the data is shorter than `bit_len` bits.
+pub fn encode_bits_into_slice(
+    input: &[u8],
+    bit_len: u64,
+    output: &mut [u8],
+) -> Result<usize, EncodeSliceError> {
+    assert!(bit_len <= input.len() as u64 * 8);
+
+    let input_len = (bit_len / 8) as usize;
+    let partial_bits = (bit_len % 8) as usize;
+
+    let mut encoder = Encoder::new();
+
+    if partial_bits != 0 {
+        encoder.set_partial_input_byte(input[input_len] >> (8 - partial_bits), partial_bits);
+    } else {
+        encoder.set_consumed_bytes_end();
+    }
+
+    let (_, produced_len, state) = encoder.step(&input[..input_len], output);
+
+    match state {
+        EncoderState::CanConsume => unreachable!("is given entire input"),
+        EncoderState::CanProduce => Err(EncodeSliceError::NeedsMoreSpace),
+        EncoderState::Terminated => Ok(produced_len),
+    }
+}
+
 #[cfg(test)]
 mod tests;
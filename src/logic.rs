@@ -0,0 +1,52 @@
+use crate::{DecodeSliceError, Run, RunPairs, RunWriter};
+
+/// Bitwise operation applied by [`combine_compressed`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BitOp {
+    /// Bits set in both inputs.
+    And,
+    /// Bits set in either input.
+    Or,
+    /// Bits set in exactly one of the inputs.
+    Xor,
+    /// Bits set in the first input but not in the second.
+    AndNot,
+}
+
+impl BitOp {
+    const fn apply(self, first: bool, second: bool) -> bool {
+        match self {
+            Self::And => first & second,
+            Self::Or => first | second,
+            Self::Xor => first ^ second,
+            Self::AndNot => first & !second,
+        }
+    }
+}
+
+/// Combines the decoded data of two encoded inputs bit by bit into a destination byte slice,
+/// returning the length of the encoded result in bytes.
+///
+/// Both inputs are processed one run at a time without being decoded into bytes. The decoded data
+/// of the shorter input is extended with zero bits to the length of the other. Both inputs must be
+/// encoded in [`Format::V1`](crate::Format::V1) using the [`DefaultCode`](crate::DefaultCode), and
+/// so is the result.
+pub fn combine_compressed(
+    first: &[u8],
+    second: &[u8],
+    op: BitOp,
+    output: &mut [u8],
+) -> Result<usize, DecodeSliceError> {
+    let mut writer = RunWriter::new(output);
+
+    for pair in RunPairs::new(first, second) {
+        let (len, first_bit, second_bit) = pair?;
+
+        writer.push(Run {
+            bit: op.apply(first_bit.unwrap_or(false), second_bit.unwrap_or(false)),
+            len,
+        })?;
+    }
+
+    Ok(writer.finish()?)
+}
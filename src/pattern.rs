@@ -0,0 +1,59 @@
+/// Minimum number of repetitions of a byte for the encoder to represent them as a pattern.
+const MIN_BYTE_REPEATS: usize = 8;
+
+/// Minimum number of repetitions of a word for the encoder to represent them as a pattern.
+const MIN_WORD_REPEATS: usize = 8;
+
+/// Number of input bytes buffered by the encoder to detect patterns, which is enough for any
+/// pattern worth a pattern symbol to be detected regardless of how the input is split into steps.
+pub(crate) const LOOKAHEAD_LEN: usize = 4 * MIN_WORD_REPEATS;
+
+/// Progress of the encoder or decoder through a pattern symbol.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum PatternPhase {
+    /// No pattern symbol is in progress.
+    Idle,
+    /// The encoder is consuming repetitions of the pattern.
+    Repeat,
+    /// The pattern symbol itself is to be encoded.
+    Symbol,
+    /// The mode and width of the pattern are to be encoded or decoded.
+    Head,
+    /// The pattern is being decoded.
+    Value,
+    /// The number of repetitions of the pattern is to be encoded or decoded.
+    Count,
+}
+
+/// Returns the pattern repeated at the start of the given bytes as a `(pattern, is word, number
+/// of repetitions)` tuple, if it is repeated enough times to be worth a pattern symbol.
+///
+/// Runs of `0x00` and `0xFF` bytes are left to the mode tables.
+pub(crate) fn detect(input: &[u8]) -> Option<(u32, bool, usize)> {
+    let &byte = input.first()?;
+
+    if byte != 0x00 && byte != 0xFF {
+        let count = input.iter().take_while(|&&next| next == byte).count();
+
+        if count >= MIN_BYTE_REPEATS {
+            return Some((byte as u32, false, count));
+        }
+    }
+
+    let word = input.get(..4)?;
+
+    if word.iter().all(|&next| next == word[0]) {
+        return None;
+    }
+
+    let count = input
+        .chunks_exact(4)
+        .take_while(|&next| next == word)
+        .count();
+
+    if count >= MIN_WORD_REPEATS {
+        Some((u32::from_be_bytes(word.try_into().unwrap()), true, count))
+    } else {
+        None
+    }
+}
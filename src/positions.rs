@@ -0,0 +1,106 @@
+use crate::{DecodeSliceError, EncodeSliceError, Run, RunWriter, Runs};
+
+/// Iterator over the bit positions of the one bits in the decoded data of an encoded input,
+/// returned by [`ones`].
+///
+/// Positions are yielded in ascending order, and iteration stops after the first error.
+#[derive(Clone, Debug)]
+pub struct Ones<'a> {
+    runs: Runs<'a>,
+    offset: u64,
+    run_end: u64,
+}
+
+impl Iterator for Ones<'_> {
+    type Item = Result<u64, DecodeSliceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.offset == self.run_end {
+            let run = match self.runs.next()? {
+                Ok(run) => run,
+                Err(err) => return Some(Err(err)),
+            };
+
+            if run.bit {
+                self.run_end = self.offset + run.len;
+            } else {
+                self.offset += run.len;
+                self.run_end = self.offset;
+            }
+        }
+
+        self.offset += 1;
+
+        Some(Ok(self.offset - 1))
+    }
+}
+
+/// Returns an iterator over the bit positions of the one bits in the decoded data of an encoded
+/// input, without decoding it into bytes.
+///
+/// The input must be encoded in [`Format::V1`](crate::Format::V1) using the
+/// [`DefaultCode`](crate::DefaultCode).
+pub fn ones(input: &[u8]) -> Ones<'_> {
+    Ones {
+        runs: Runs::new(input),
+        offset: 0,
+        run_end: 0,
+    }
+}
+
+/// Encodes data of the given length in bits whose one bits are at the given positions into a
+/// destination byte slice, returning the length of the encoded data in bytes.
+///
+/// The data is encoded in [`Format::V1`](crate::Format::V1) using the
+/// [`DefaultCode`](crate::DefaultCode).
+///
+/// # Panics
+///
+/// Panics if the positions are not in strictly ascending order or not less than the length.
+pub fn encode_positions(
+    positions: impl IntoIterator<Item = u64>,
+    len: u64,
+    output: &mut [u8],
+) -> Result<usize, EncodeSliceError> {
+    let mut writer = RunWriter::new(output);
+
+    let mut offset = 0;
+    let mut ones = 0;
+
+    for position in positions {
+        assert!(
+            position >= offset + ones,
+            "positions must be strictly ascending"
+        );
+        assert!(position < len, "position out of bounds");
+
+        if position != offset + ones {
+            writer.push(Run {
+                bit: true,
+                len: ones,
+            })?;
+
+            writer.push(Run {
+                bit: false,
+                len: position - offset - ones,
+            })?;
+
+            offset = position;
+            ones = 0;
+        }
+
+        ones += 1;
+    }
+
+    writer.push(Run {
+        bit: true,
+        len: ones,
+    })?;
+
+    writer.push(Run {
+        bit: false,
+        len: len - offset - ones,
+    })?;
+
+    writer.finish()
+}
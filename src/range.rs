@@ -0,0 +1,667 @@
+use crate::{DecodeSliceError, DecoderState, EncodeSliceError, EncoderState};
+
+/// Range below which the range coder shifts out a byte.
+const TOP: u32 = 1 << 24;
+
+/// Precision of the probabilities in bits.
+const PROB_BITS: u32 = 11;
+
+/// Adaptation rate of the probabilities, as a shift.
+const MOVE_BITS: u32 = 5;
+
+/// Number of contexts per mode, indexed by bit length of the run length (clamped).
+const CONTEXTS: usize = 32;
+
+/// Number of bytes flushed at the end of the encoded data.
+const FLUSH_LEN: u32 = 5;
+
+/// Adaptive probabilities of the binary decisions coding run lengths.
+///
+/// Each value is coded as the unary representation of its bit length followed by its bits below
+/// the leading one. The unary decisions and the first bit below the leading one are coded with
+/// adaptive probabilities indexed by mode and position, and the remaining bits are coded directly.
+#[derive(Clone, Copy, Debug)]
+struct Model {
+    exponent: [[u16; CONTEXTS]; 2],
+    mantissa: [[u16; CONTEXTS]; 2],
+}
+
+impl Model {
+    const fn new() -> Self {
+        Self {
+            exponent: [[1 << (PROB_BITS - 1); CONTEXTS]; 2],
+            mantissa: [[1 << (PROB_BITS - 1); CONTEXTS]; 2],
+        }
+    }
+}
+
+/// Returns the number of significant bits of the given (nonzero) value.
+fn bit_len(value: u64) -> u32 {
+    u64::BITS - value.leading_zeros()
+}
+
+/// Returns the context of the given position.
+fn context(position: u32) -> usize {
+    (position as usize).min(CONTEXTS - 1)
+}
+
+/// Position of the encoder within the encoded data.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum EncoderStep {
+    /// More input is required before the next value can be coded.
+    Input,
+    /// The initial mode is to be coded.
+    Header(bool),
+    /// The given unary decision of the bit length of the value is to be coded.
+    Exponent(u32),
+    /// The given number of bits of the value are left to be coded.
+    Mantissa(u32),
+    /// The given number of bytes are left to be flushed.
+    Flush(u32),
+    /// The encoded data has terminated.
+    Done,
+}
+
+/// Streaming encoder context for the high-ratio variant, coding run lengths with an adaptive
+/// binary range coder as described in the README.
+///
+/// The encoded data is not compatible with [`crate::Decoder`], and must be decoded using
+/// [`RangeDecoder`] instead.
+#[derive(Debug)]
+pub struct RangeEncoder {
+    low: u64,
+    range: u32,
+    cache: u8,
+    cache_len: u64,
+
+    pending_byte: u8,
+    pending_fill: u8,
+    pending_len: u64,
+
+    input_data: u8,
+    input_bits: usize,
+
+    run_len: u64,
+    value: u64,
+    step: EncoderStep,
+    model: Model,
+
+    run_mode: bool,
+    value_mode: bool,
+    header: bool,
+    started: bool,
+    input_end: bool,
+}
+
+impl RangeEncoder {
+    /// Constructs a new encoder instance in its initial state.
+    pub const fn new() -> Self {
+        Self {
+            low: 0,
+            range: u32::MAX,
+            cache: 0,
+            cache_len: 1,
+
+            pending_byte: 0,
+            pending_fill: 0,
+            pending_len: 0,
+
+            input_data: 0,
+            input_bits: 0,
+
+            run_len: 0,
+            value: 0,
+            step: EncoderStep::Input,
+            model: Model::new(),
+
+            run_mode: false,
+            value_mode: false,
+            header: false,
+            started: false,
+            input_end: false,
+        }
+    }
+
+    /// Resets this encoder instance to its initial state.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Steps this encoder instance, returning a `(bytes consumed, bytes produced, state)` tuple.
+    pub fn step(&mut self, consumed: &[u8], produced: &mut [u8]) -> (usize, usize, EncoderState) {
+        let mut consumed_len = 0;
+        let mut produced_len = 0;
+
+        loop {
+            if self.produce(produced, &mut produced_len) {
+                return (consumed_len, produced_len, EncoderState::CanProduce);
+            }
+
+            match self.step {
+                EncoderStep::Input => {
+                    if self.consume(consumed, &mut consumed_len) {
+                        return (consumed_len, produced_len, EncoderState::CanConsume);
+                    }
+                }
+                EncoderStep::Done => break,
+                _ => self.encode_next(),
+            }
+        }
+
+        (consumed_len, produced_len, EncoderState::Terminated)
+    }
+
+    /// Informs the encoder that no further input bytes are available.
+    pub fn set_consumed_bytes_end(&mut self) {
+        self.input_end = true;
+    }
+
+    fn consume(&mut self, consumed: &[u8], consumed_len: &mut usize) -> bool {
+        loop {
+            if self.input_bits == 0 {
+                if let Some(&byte) = consumed.get(*consumed_len) {
+                    self.input_data = byte;
+                    self.input_bits = 8;
+                    *consumed_len += 1;
+                } else if !self.input_end {
+                    return true;
+                } else if !self.header {
+                    self.header = true;
+                    self.step = EncoderStep::Header(false);
+                    return false;
+                } else {
+                    let run_len = core::mem::take(&mut self.run_len);
+                    self.queue_value(run_len + 1);
+                    return false;
+                }
+            }
+
+            if !self.header {
+                self.header = true;
+                self.run_mode = self.input_data & 0x80 != 0;
+                self.step = EncoderStep::Header(self.run_mode);
+                return false;
+            }
+
+            let count = if self.run_mode {
+                self.input_data.leading_ones()
+            } else {
+                self.input_data.leading_zeros()
+            } as usize;
+
+            let count = count.min(self.input_bits);
+
+            self.input_data = self.input_data.checked_shl(count as u32).unwrap_or(0);
+            self.input_bits -= count;
+            self.run_len += count as u64;
+
+            if self.input_bits != 0 {
+                let run_len = core::mem::take(&mut self.run_len);
+                self.queue_value(run_len + 1);
+                return false;
+            }
+        }
+    }
+
+    /// Queues the given value for coding in the current mode, where a value of one terminates the
+    /// encoded data and any other value represents a run one bit shorter.
+    fn queue_value(&mut self, value: u64) {
+        self.value = value;
+        self.value_mode = self.run_mode;
+        self.run_mode = !self.run_mode;
+        self.step = EncoderStep::Exponent(0);
+    }
+
+    fn encode_next(&mut self) {
+        let mode = self.value_mode as usize;
+        let value_bits = bit_len(self.value);
+
+        match self.step {
+            EncoderStep::Header(bit) => {
+                self.encode_direct(bit);
+                self.step = EncoderStep::Input;
+            }
+            EncoderStep::Exponent(position) => {
+                let bit = position + 1 < value_bits;
+                let mut prob = self.model.exponent[mode][context(position)];
+                self.encode_bit(&mut prob, bit);
+                self.model.exponent[mode][context(position)] = prob;
+
+                self.step = if bit {
+                    EncoderStep::Exponent(position + 1)
+                } else if value_bits > 1 {
+                    EncoderStep::Mantissa(value_bits - 1)
+                } else {
+                    self.value_done()
+                };
+            }
+            EncoderStep::Mantissa(remaining) => {
+                let bit = (self.value >> (remaining - 1)) & 1 != 0;
+
+                if remaining == value_bits - 1 {
+                    let mut prob = self.model.mantissa[mode][context(value_bits)];
+                    self.encode_bit(&mut prob, bit);
+                    self.model.mantissa[mode][context(value_bits)] = prob;
+                } else {
+                    self.encode_direct(bit);
+                }
+
+                self.step = if remaining > 1 {
+                    EncoderStep::Mantissa(remaining - 1)
+                } else {
+                    self.value_done()
+                };
+            }
+            EncoderStep::Flush(remaining) => {
+                self.shift_low();
+
+                self.step = if remaining > 1 {
+                    EncoderStep::Flush(remaining - 1)
+                } else {
+                    EncoderStep::Done
+                };
+            }
+            EncoderStep::Input | EncoderStep::Done => unreachable!(),
+        }
+    }
+
+    fn value_done(&self) -> EncoderStep {
+        if self.value == 1 {
+            EncoderStep::Flush(FLUSH_LEN)
+        } else {
+            EncoderStep::Input
+        }
+    }
+
+    fn encode_bit(&mut self, prob: &mut u16, bit: bool) {
+        let bound = (self.range >> PROB_BITS) * *prob as u32;
+
+        if bit {
+            self.low += bound as u64;
+            self.range -= bound;
+            *prob -= *prob >> MOVE_BITS;
+        } else {
+            self.range = bound;
+            *prob += ((1 << PROB_BITS) - *prob) >> MOVE_BITS;
+        }
+
+        self.normalize();
+    }
+
+    fn encode_direct(&mut self, bit: bool) {
+        self.range >>= 1;
+
+        if bit {
+            self.low += self.range as u64;
+        }
+
+        self.normalize();
+    }
+
+    fn normalize(&mut self) {
+        // A single shift always suffices since the range shrinks by less than 8 bits per decision,
+        // so that the bytes it produces can be drained before the next decision.
+        if self.range < TOP {
+            self.range <<= 8;
+            self.shift_low();
+        }
+    }
+
+    fn shift_low(&mut self) {
+        debug_assert!(self.pending_len == 0);
+
+        if (self.low as u32) < 0xFF00_0000 || self.low >> 32 != 0 {
+            let carry = (self.low >> 32) as u8;
+
+            self.pending_byte = self.cache.wrapping_add(carry);
+            self.pending_fill = 0xFF_u8.wrapping_add(carry);
+            self.pending_len = self.cache_len;
+
+            // The first byte is always zero as the initial range cannot carry into it, so it is
+            // omitted from the encoded data.
+            if !self.started {
+                self.started = true;
+                self.pending_byte = self.pending_fill;
+                self.pending_len -= 1;
+            }
+
+            self.cache = (self.low >> 24) as u8;
+            self.cache_len = 0;
+        }
+
+        self.cache_len += 1;
+        self.low = (self.low & 0x00FF_FFFF) << 8;
+    }
+
+    fn produce(&mut self, produced: &mut [u8], produced_len: &mut usize) -> bool {
+        while self.pending_len != 0 {
+            let Some(byte) = produced.get_mut(*produced_len) else {
+                return true;
+            };
+
+            *byte = self.pending_byte;
+            self.pending_byte = self.pending_fill;
+            self.pending_len -= 1;
+            *produced_len += 1;
+        }
+
+        false
+    }
+}
+
+impl Default for RangeEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Position of the decoder within the encoded data.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum DecoderStep {
+    /// The initial mode is to be decoded.
+    Header,
+    /// The given unary decision of the bit length of the value is to be decoded.
+    Exponent(u32),
+    /// The given number of bits of the value are left to be decoded.
+    Mantissa(u32),
+    /// The encoded data is to be checked once fully consumed.
+    Check,
+}
+
+/// Streaming decoder context for the high-ratio variant, decoding data encoded by
+/// [`RangeEncoder`].
+///
+/// Its state takes 304 bytes on a 32-bit target (320 bytes on a 64-bit target), 256 of which are
+/// the adaptive probabilities.
+#[derive(Debug)]
+pub struct RangeDecoder {
+    code: u32,
+    range: u32,
+    init_len: usize,
+
+    queued_bits: usize,
+    output_bits: usize,
+    output_data: u8,
+
+    value: u64,
+    value_bits: u32,
+    step: DecoderStep,
+    model: Model,
+
+    mode: bool,
+    queued_mode: bool,
+    symbol_term: bool,
+    symbol_fail: bool,
+}
+
+impl RangeDecoder {
+    /// Constructs a new decoder instance in its initial state.
+    pub const fn new() -> Self {
+        Self {
+            code: 0,
+            range: u32::MAX,
+            init_len: 0,
+
+            queued_bits: 0,
+            output_bits: 0,
+            output_data: 0,
+
+            value: 0,
+            value_bits: 0,
+            step: DecoderStep::Header,
+            model: Model::new(),
+
+            mode: false,
+            queued_mode: false,
+            symbol_term: false,
+            symbol_fail: false,
+        }
+    }
+
+    /// Resets this decoder instance to its initial state.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Steps this decoder instance, returning a `(bytes consumed, bytes produced, state)` tuple.
+    pub fn step(&mut self, consumed: &[u8], produced: &mut [u8]) -> (usize, usize, DecoderState) {
+        let mut consumed_len = 0;
+        let mut produced_len = 0;
+
+        loop {
+            if self.consume(consumed, &mut consumed_len) {
+                return (consumed_len, produced_len, DecoderState::CanConsume);
+            }
+
+            if self.produce(produced, &mut produced_len) {
+                return (consumed_len, produced_len, DecoderState::CanProduce);
+            }
+
+            if self.symbol_term {
+                break;
+            }
+        }
+
+        let state = DecoderState::Terminated {
+            corrupted: self.symbol_fail,
+            unaligned: self.output_bits != 0,
+        };
+
+        (consumed_len, produced_len, state)
+    }
+
+    fn consume(&mut self, consumed: &[u8], consumed_len: &mut usize) -> bool {
+        while self.queued_bits == 0 && !self.symbol_term {
+            while self.init_len < 4 || self.range < TOP {
+                let Some(&byte) = consumed.get(*consumed_len) else {
+                    return true;
+                };
+
+                self.code = (self.code << 8) | byte as u32;
+                *consumed_len += 1;
+
+                if self.init_len < 4 {
+                    self.init_len += 1;
+                } else {
+                    self.range <<= 8;
+                }
+            }
+
+            self.decode_next();
+        }
+
+        false
+    }
+
+    fn decode_next(&mut self) {
+        let mode = self.mode as usize;
+
+        match self.step {
+            DecoderStep::Header => {
+                self.mode = self.decode_direct();
+                self.step = DecoderStep::Exponent(0);
+            }
+            DecoderStep::Exponent(position) => {
+                let mut prob = self.model.exponent[mode][context(position)];
+                let bit = self.decode_bit(&mut prob);
+                self.model.exponent[mode][context(position)] = prob;
+
+                if !bit {
+                    self.value = 1;
+                    self.value_bits = position + 1;
+
+                    if position == 0 {
+                        self.value_done();
+                    } else {
+                        self.step = DecoderStep::Mantissa(position);
+                    }
+                } else if position + 1 == u64::BITS {
+                    self.fail();
+                } else {
+                    self.step = DecoderStep::Exponent(position + 1);
+                }
+            }
+            DecoderStep::Mantissa(remaining) => {
+                let bit = if remaining == self.value_bits - 1 {
+                    let mut prob = self.model.mantissa[mode][context(self.value_bits)];
+                    let bit = self.decode_bit(&mut prob);
+                    self.model.mantissa[mode][context(self.value_bits)] = prob;
+                    bit
+                } else {
+                    self.decode_direct()
+                };
+
+                self.value = (self.value << 1) | bit as u64;
+
+                if remaining > 1 {
+                    self.step = DecoderStep::Mantissa(remaining - 1);
+                } else {
+                    self.value_done();
+                }
+            }
+            DecoderStep::Check => {
+                self.symbol_term = true;
+                self.symbol_fail = self.code != 0;
+            }
+        }
+    }
+
+    fn value_done(&mut self) {
+        if self.value == 1 {
+            self.step = DecoderStep::Check;
+            return;
+        }
+
+        match usize::try_from(self.value - 1) {
+            Ok(count) => {
+                self.queued_bits = count;
+                self.queued_mode = self.mode;
+                self.mode = !self.mode;
+                self.step = DecoderStep::Exponent(0);
+            }
+            Err(_) => self.fail(),
+        }
+    }
+
+    fn fail(&mut self) {
+        self.symbol_term = true;
+        self.symbol_fail = true;
+    }
+
+    fn decode_bit(&mut self, prob: &mut u16) -> bool {
+        let bound = (self.range >> PROB_BITS) * *prob as u32;
+
+        if self.code < bound {
+            self.range = bound;
+            *prob += ((1 << PROB_BITS) - *prob) >> MOVE_BITS;
+            false
+        } else {
+            self.code -= bound;
+            self.range -= bound;
+            *prob -= *prob >> MOVE_BITS;
+            true
+        }
+    }
+
+    fn decode_direct(&mut self) -> bool {
+        self.range >>= 1;
+
+        if self.code >= self.range {
+            self.code -= self.range;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn produce(&mut self, produced: &mut [u8], produced_len: &mut usize) -> bool {
+        if self.output_bits == 0 && self.queued_bits >= 8 {
+            let transfer = (self.queued_bits / 8).min(produced.len() - *produced_len);
+
+            if transfer == 0 {
+                return true;
+            }
+
+            let slice = &mut produced[*produced_len..][..transfer];
+
+            if self.queued_mode {
+                slice.fill(0xFF);
+            } else {
+                slice.fill(0x00);
+            }
+
+            self.queued_bits -= transfer * 8;
+            *produced_len += transfer;
+        } else if self.output_bits != 8 && self.queued_bits != 0 {
+            let amount = (8 - self.output_bits).min(self.queued_bits);
+
+            let mut word = 0;
+
+            if self.queued_mode {
+                word = (1 << amount) - 1;
+            }
+
+            self.output_data <<= amount;
+            self.output_data |= word;
+            self.output_bits += amount;
+            self.queued_bits -= amount;
+        } else if self.output_bits == 8 {
+            if let Some(byte) = produced.get_mut(*produced_len) {
+                *byte = self.output_data;
+                self.output_data = 0;
+                self.output_bits = 0;
+                *produced_len += 1;
+            } else {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+impl Default for RangeDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convenient helper function to directly encode arbitrary data into a destination byte slice
+/// using the high-ratio variant.
+pub fn encode_range_into_slice(input: &[u8], output: &mut [u8]) -> Result<usize, EncodeSliceError> {
+    let mut encoder = RangeEncoder::new();
+    encoder.set_consumed_bytes_end();
+
+    let (_, produced_len, state) = encoder.step(input, output);
+
+    match state {
+        EncoderState::CanConsume => unreachable!("is given entire input"),
+        EncoderState::CanProduce => Err(EncodeSliceError::NeedsMoreSpace),
+        EncoderState::Terminated => Ok(produced_len),
+    }
+}
+
+/// Convenient helper function to directly decode arbitrary data encoded using the high-ratio
+/// variant from a destination byte slice.
+pub fn decode_range_from_slice(input: &[u8], output: &mut [u8]) -> Result<usize, DecodeSliceError> {
+    let mut decoder = RangeDecoder::new();
+
+    let (_, produced_len, state) = decoder.step(input, output);
+
+    match state {
+        DecoderState::CanConsume => Err(DecodeSliceError::TruncatedInput),
+        DecoderState::CanProduce => Err(DecodeSliceError::NeedsMoreSpace),
+        DecoderState::Terminated {
+            corrupted,
+            unaligned,
+        } => {
+            if corrupted {
+                Err(DecodeSliceError::Corrupted)
+            } else if unaligned {
+                Err(DecodeSliceError::Unaligned)
+            } else {
+                Ok(produced_len)
+            }
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{DecodeSliceError, Runs};
+
+/// Counts the number of one bits in the decoded data of an encoded input.
+///
+/// The input must be encoded in [`Format::V1`](crate::Format::V1) using the
+/// [`DefaultCode`](crate::DefaultCode).
+pub fn count_ones(input: &[u8]) -> Result<u64, DecodeSliceError> {
+    let mut count = 0;
+
+    for run in Runs::new(input) {
+        let run = run?;
+
+        if run.bit {
+            count += run.len;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Counts the number of one bits preceding the given bit position in the decoded data of an
+/// encoded input.
+///
+/// Positions past the end of the decoded data count all of its one bits. The encoded input is only
+/// read up to the given position, so that any errors located after it are not reported. The input
+/// must be encoded in [`Format::V1`](crate::Format::V1) using the
+/// [`DefaultCode`](crate::DefaultCode).
+pub fn rank(input: &[u8], bit_pos: u64) -> Result<u64, DecodeSliceError> {
+    let mut count = 0;
+    let mut offset = 0;
+
+    for run in Runs::new(input) {
+        if offset >= bit_pos {
+            break;
+        }
+
+        let run = run?;
+
+        if run.bit {
+            count += run.len.min(bit_pos - offset);
+        }
+
+        offset += run.len;
+    }
+
+    Ok(count)
+}
+
+/// Locates the bit position of the one bit preceded by exactly `k` other one bits in the decoded
+/// data of an encoded input, or `None` if the decoded data contains no more than `k` one bits.
+///
+/// The encoded input is only read up to the located bit, so that any errors located after it are
+/// not reported. The input must be encoded in [`Format::V1`](crate::Format::V1) using the
+/// [`DefaultCode`](crate::DefaultCode).
+pub fn select(input: &[u8], k: u64) -> Result<Option<u64>, DecodeSliceError> {
+    let mut count = 0;
+    let mut offset = 0;
+
+    for run in Runs::new(input) {
+        let run = run?;
+
+        if run.bit {
+            if k - count < run.len {
+                return Ok(Some(offset + (k - count)));
+            }
+
+            count += run.len;
+        }
+
+        offset += run.len;
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,52 @@
+/// Number of runs after which the statistics of a mode are halved, so that the parameters track
+/// the recent run lengths.
+const RESET_COUNT: u8 = 16;
+
+/// Largest Golomb-Rice parameter, keeping the longest non-escape symbols within 24 bits.
+const MAX_PARAM: usize = 12;
+
+/// Adaptive Golomb-Rice parameters for both modes, derived from the recent run lengths of each
+/// mode in the manner of LOCO-I.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RiceParams {
+    sums: [u16; 2],
+    counts: [u8; 2],
+}
+
+impl RiceParams {
+    /// Constructs the initial parameters.
+    pub const fn new() -> Self {
+        Self {
+            sums: [4; 2],
+            counts: [1; 2],
+        }
+    }
+
+    /// Returns the current parameter of the given mode.
+    pub fn param(&self, mode: bool) -> usize {
+        let sum = self.sums[mode as usize] as usize;
+        let count = self.counts[mode as usize] as usize;
+
+        let mut param = 0;
+
+        while param < MAX_PARAM && count << param < sum {
+            param += 1;
+        }
+
+        param
+    }
+
+    /// Updates the parameter of the given mode following a run of the given length.
+    pub fn update(&mut self, mode: bool, len: usize) {
+        let sum = &mut self.sums[mode as usize];
+        let count = &mut self.counts[mode as usize];
+
+        *sum = sum.saturating_add(len.min(u16::MAX as usize) as u16);
+        *count += 1;
+
+        if *count == RESET_COUNT {
+            *sum /= 2;
+            *count /= 2;
+        }
+    }
+}
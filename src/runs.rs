@@ -0,0 +1,219 @@
+use crate::{DecodeSliceError, EncodeSliceError, Encoder, EncoderState, SymbolReader};
+
+/// Run of identical bits in decoded data.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Run {
+    /// The value of every bit in the run.
+    pub bit: bool,
+    /// The number of bits in the run.
+    pub len: u64,
+}
+
+/// Iterator over the maximal runs of encoded data held in a byte slice.
+#[derive(Clone, Debug)]
+pub(crate) struct Runs<'a> {
+    reader: SymbolReader<'a>,
+    pending: Option<Run>,
+    done: bool,
+}
+
+impl<'a> Runs<'a> {
+    /// Constructs a new run iterator over the given encoded data.
+    pub const fn new(input: &'a [u8]) -> Self {
+        Self {
+            reader: SymbolReader::new(input),
+            pending: None,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for Runs<'_> {
+    type Item = Result<Run, DecodeSliceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let bit = self.reader.mode();
+
+            let symbol = match self.reader.next_symbol() {
+                Ok(symbol) => symbol,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            };
+
+            if symbol.term {
+                self.done = true;
+
+                if !self.reader.is_padded() {
+                    return Some(Err(DecodeSliceError::Corrupted));
+                }
+
+                break;
+            }
+
+            if symbol.count == 0 {
+                continue;
+            }
+
+            let len = symbol.count as u64;
+
+            match &mut self.pending {
+                Some(run) if run.bit == bit => run.len += len,
+                pending => {
+                    if let Some(run) = pending.replace(Run { bit, len }) {
+                        return Some(Ok(run));
+                    }
+                }
+            }
+        }
+
+        self.pending.take().map(Ok)
+    }
+}
+
+/// Writer encoding runs of bits into a byte slice.
+#[derive(Debug)]
+pub(crate) struct RunWriter<'a> {
+    encoder: Encoder,
+    output: &'a mut [u8],
+    output_len: usize,
+}
+
+impl<'a> RunWriter<'a> {
+    /// Constructs a new run writer encoding into the given output.
+    pub fn new(output: &'a mut [u8]) -> Self {
+        Self::resume(Encoder::new(), output, 0)
+    }
+
+    /// Constructs a run writer continuing from the given encoder, which has already produced the
+    /// given number of bytes of the output.
+    pub fn resume(encoder: Encoder, output: &'a mut [u8], output_len: usize) -> Self {
+        Self {
+            encoder,
+            output,
+            output_len,
+        }
+    }
+
+    /// Appends a run to the encoded data.
+    pub fn push(&mut self, run: Run) -> Result<(), EncodeSliceError> {
+        if run.len == 0 {
+            return Ok(());
+        }
+
+        if run.bit != self.encoder.queued_mode {
+            self.encoder.queued_mode = run.bit;
+            self.encoder.queued_done = true;
+
+            while self.encoder.queued_done {
+                if self.encoder.produce(self.output, &mut self.output_len) {
+                    return Err(EncodeSliceError::NeedsMoreSpace);
+                }
+            }
+        }
+
+        let mut len = run.len;
+
+        while len != 0 {
+            if self.encoder.queued_bits == usize::MAX {
+                // The queued run is too long to be counted on this target, so part of it is
+                // produced as a continuation symbol.
+                let mode = self.encoder.queued_mode;
+                self.encoder.queued_bits = self.encoder.produce_run(mode, usize::MAX).unwrap();
+
+                if self.encoder.produce(self.output, &mut self.output_len) {
+                    return Err(EncodeSliceError::NeedsMoreSpace);
+                }
+            }
+
+            let amount = len.min((usize::MAX - self.encoder.queued_bits) as u64);
+
+            self.encoder.queued_bits += amount as usize;
+            len -= amount;
+        }
+
+        Ok(())
+    }
+
+    /// Terminates the encoded data, returning its length in bytes.
+    pub fn finish(mut self) -> Result<usize, EncodeSliceError> {
+        self.encoder.set_consumed_bytes_end();
+
+        let (_, produced_len, state) = self.encoder.step(&[], &mut self.output[self.output_len..]);
+
+        match state {
+            EncoderState::CanConsume => unreachable!("is given entire input"),
+            EncoderState::CanProduce => Err(EncodeSliceError::NeedsMoreSpace),
+            EncoderState::Terminated => Ok(self.output_len + produced_len),
+        }
+    }
+}
+
+/// Iterator over the segments of the decoded data of two encoded inputs within which neither
+/// input changes bit, yielding `(length, first bit, second bit)` tuples.
+///
+/// Segments past the end of the decoded data of the shorter input report its bit as `None`.
+#[derive(Clone, Debug)]
+pub(crate) struct RunPairs<'a> {
+    first: Runs<'a>,
+    second: Runs<'a>,
+    first_run: Option<Run>,
+    second_run: Option<Run>,
+}
+
+impl<'a> RunPairs<'a> {
+    /// Constructs a new run pair iterator over the given encoded data.
+    pub const fn new(first: &'a [u8], second: &'a [u8]) -> Self {
+        Self {
+            first: Runs::new(first),
+            second: Runs::new(second),
+            first_run: None,
+            second_run: None,
+        }
+    }
+
+    fn refill(runs: &mut Runs<'_>, run: &mut Option<Run>) -> Result<(), DecodeSliceError> {
+        if run.is_none_or(|run| run.len == 0) {
+            *run = runs.next().transpose()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Iterator for RunPairs<'_> {
+    type Item = Result<(u64, Option<bool>, Option<bool>), DecodeSliceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(err) = Self::refill(&mut self.first, &mut self.first_run)
+            .and_then(|()| Self::refill(&mut self.second, &mut self.second_run))
+        {
+            self.first_run = None;
+            self.second_run = None;
+
+            return Some(Err(err));
+        }
+
+        let len = match (self.first_run, self.second_run) {
+            (None, None) => return None,
+            (Some(first), None) => first.len,
+            (None, Some(second)) => second.len,
+            (Some(first), Some(second)) => first.len.min(second.len),
+        };
+
+        let take = |run: &mut Option<Run>| {
+            run.as_mut().map(|run| {
+                run.len -= len;
+                run.bit
+            })
+        };
+
+        Some(Ok((
+            len,
+            take(&mut self.first_run),
+            take(&mut self.second_run),
+        )))
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{DecodeSliceError, Encoder, Run, RunWriter, Runs};
+use core::ops::Range;
+
+/// Concatenates two encoded inputs into a destination byte slice, such that the result decodes to
+/// the decoded data of the first input followed by that of the second.
+///
+/// The first input is copied as-is save for its trailing run, and the second input is re-encoded
+/// one run at a time, so that this operation never decodes either input into bytes. Its cost is
+/// nonetheless proportional to the length of the second input rather than constant.
+///
+/// Both inputs must be encoded in [`Format::V1`](crate::Format::V1) using the
+/// [`DefaultCode`](crate::DefaultCode).
+pub fn concat(first: &[u8], second: &[u8], output: &mut [u8]) -> Result<usize, DecodeSliceError> {
+    let (encoder, kept_len) = Encoder::resume_from(first)?;
+
+    let Some(kept) = output.get_mut(..kept_len) else {
+        return Err(DecodeSliceError::NeedsMoreSpace);
+    };
+
+    kept.copy_from_slice(&first[..kept_len]);
+
+    let mut writer = RunWriter::resume(encoder, output, kept_len);
+
+    for run in Runs::new(second) {
+        writer.push(run?)?;
+    }
+
+    Ok(writer.finish()?)
+}
+
+/// Encodes the given range of bits of the decoded data of an encoded input into a destination
+/// byte slice, returning the length of the encoded range in bytes.
+///
+/// Runs are clipped to the range rather than decoded, and the part of the range extending past
+/// the end of the decoded data (if any) is ignored. The encoded input is only read up to the end
+/// of the range, so that any errors located after it are not reported.
+///
+/// The input must be encoded in [`Format::V1`](crate::Format::V1) using the
+/// [`DefaultCode`](crate::DefaultCode), and so is the output.
+pub fn slice_compressed(
+    input: &[u8],
+    bit_range: Range<u64>,
+    output: &mut [u8],
+) -> Result<usize, DecodeSliceError> {
+    let mut writer = RunWriter::new(output);
+    let mut offset = 0;
+
+    for run in Runs::new(input) {
+        if offset >= bit_range.end {
+            break;
+        }
+
+        let run = run?;
+
+        let start = offset.max(bit_range.start);
+        let end = (offset + run.len).min(bit_range.end);
+
+        if start < end {
+            writer.push(Run {
+                bit: run.bit,
+                len: end - start,
+            })?;
+        }
+
+        offset += run.len;
+    }
+
+    Ok(writer.finish()?)
+}
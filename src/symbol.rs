@@ -0,0 +1,127 @@
+use crate::{Code, DecodeSliceError, DefaultCode, Tables};
+
+/// Prefix code symbol decoded from the front of a window of at least 24 bits.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Symbol {
+    /// The length of the symbol in bits.
+    pub len: usize,
+    /// The number of bits of data represented by the symbol.
+    pub count: usize,
+    /// Whether the symbol is a continuated symbol.
+    pub cont: bool,
+    /// Whether the symbol is the termination symbol.
+    pub term: bool,
+}
+
+impl Symbol {
+    /// Decodes the symbol at the front of the given (left-aligned) window in the given mode.
+    pub fn decode<C: Code>(window: u32, mode: bool) -> Self {
+        let len;
+        let count;
+        let mut cont = false;
+        let mut term = false;
+
+        let bases = &Tables::<C>::BASES[mode as usize];
+        let prefix_len = window.leading_zeros() as usize;
+
+        if prefix_len < 12 {
+            let payload_bits = C::PAYLOAD_BITS[mode as usize][prefix_len] as usize;
+            len = prefix_len + 1 + payload_bits;
+
+            let payload = (window << (prefix_len + 1))
+                .checked_shr(32 - payload_bits as u32)
+                .unwrap_or(0);
+
+            count = bases[prefix_len] + payload as usize;
+        } else {
+            len = 24;
+
+            let payload = ((window >> 8) as u16) & 0b1111_1111_1111;
+
+            if payload == 0xFFF {
+                term = true;
+                count = 0;
+            } else if payload == 0xFFE {
+                count = 0;
+            } else {
+                count = bases[12] + payload as usize;
+
+                if payload == 0xFFD {
+                    cont = true;
+                }
+            }
+        }
+
+        Self {
+            len,
+            count,
+            cont,
+            term,
+        }
+    }
+}
+
+/// Reader decoding the symbols of encoded data held in a byte slice.
+#[derive(Clone, Debug)]
+pub(crate) struct SymbolReader<'a> {
+    input: &'a [u8],
+    bit_pos: u64,
+    mode: bool,
+}
+
+impl<'a> SymbolReader<'a> {
+    /// Constructs a new symbol reader positioned at the start of the given encoded data.
+    pub const fn new(input: &'a [u8]) -> Self {
+        Self {
+            input,
+            bit_pos: 0,
+            mode: false,
+        }
+    }
+
+    /// Returns the position of the next symbol in bits.
+    pub const fn bit_pos(&self) -> u64 {
+        self.bit_pos
+    }
+
+    /// Returns the mode the next symbol will be decoded in.
+    pub const fn mode(&self) -> bool {
+        self.mode
+    }
+
+    /// Decodes the next symbol and advances past it.
+    pub fn next_symbol(&mut self) -> Result<Symbol, DecodeSliceError> {
+        if self.bit_pos + 24 > self.input.len() as u64 * 8 {
+            return Err(DecodeSliceError::TruncatedInput);
+        }
+
+        let offset = (self.bit_pos / 8) as usize;
+        let mut window = 0;
+
+        for (index, &byte) in self.input[offset..].iter().take(4).enumerate() {
+            window |= (byte as u32) << (24 - 8 * index);
+        }
+
+        let symbol = Symbol::decode::<DefaultCode>(window << (self.bit_pos % 8), self.mode);
+
+        self.bit_pos += symbol.len as u64;
+
+        if !symbol.cont {
+            self.mode = !self.mode;
+        }
+
+        Ok(symbol)
+    }
+
+    /// Returns whether the bits following the current position up to the next byte boundary are
+    /// all zero, as is expected following the termination symbol.
+    pub fn is_padded(&self) -> bool {
+        let shift = self.bit_pos % 8;
+
+        if shift == 0 {
+            return true;
+        }
+
+        self.input[(self.bit_pos / 8) as usize] & (0xFF >> shift) == 0
+    }
+}
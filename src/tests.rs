@@ -1,4 +1,5 @@
 use crate::*;
+use core::ops::Range;
 
 fn decode_slice_alternative(encoded_ref: &[u8], decoded_ref: &[u8], recoded_ref: &[u8]) {
     let mut encoded_buf = [0u8; 4096];
@@ -44,6 +45,15 @@ fn encode_slice_with_error<const N: usize>(payload: &[u8], error: EncodeSliceErr
     assert_eq!(encode_into_slice(payload, &mut [0u8; N]), Err(error));
 }
 
+#[test]
+fn decoder_state_size() {
+    // 20 bytes on a 32-bit target, as stated in the README.
+    assert_eq!(
+        core::mem::size_of::<Decoder>(),
+        3 * core::mem::size_of::<usize>() + 8
+    );
+}
+
 #[test]
 fn decode_slice_truncated_input() {
     decode_slice_with_error::<32>(&[0b00111000], DecodeSliceError::TruncatedInput);
@@ -521,3 +531,2503 @@ fn encode_streaming() {
         (0, 0, EncoderState::Terminated)
     );
 }
+
+#[test]
+fn encode_one_byte_at_a_time() {
+    for input in [&[0x01][..], &[0x00], &[0x0F, 0xF0], &[]] {
+        let mut expected = [0u8; 64];
+        let expected_len = encode_into_slice(input, &mut expected).unwrap();
+
+        let mut encoder = Encoder::new();
+        encoder.set_consumed_bytes_end();
+
+        let mut encoded = [0u8; 64];
+        let mut encoded_len = 0;
+        let mut consumed = input;
+
+        loop {
+            let (consumed_len, produced_len, state) =
+                encoder.step(consumed, &mut encoded[encoded_len..][..1]);
+
+            consumed = &consumed[consumed_len..];
+            encoded_len += produced_len;
+
+            if state == EncoderState::Terminated {
+                break;
+            }
+        }
+
+        assert_eq!(&encoded[..encoded_len], &expected[..expected_len]);
+    }
+}
+
+fn format_v2_round_trip(decoded_ref: &[u8], adaptive: bool) -> usize {
+    let mut encoder = Encoder::with_format(Format::V2);
+    encoder.set_consumed_bytes_end();
+
+    if adaptive {
+        encoder.set_adaptive();
+    }
+
+    let mut encoded = [0u8; 8192];
+    let (_, encoded_len, state) = encoder.step(decoded_ref, &mut encoded);
+    assert_eq!(state, EncoderState::Terminated);
+
+    format_v2_decodes(&encoded[..encoded_len], decoded_ref);
+
+    encoded_len
+}
+
+#[test]
+fn format_v2_round_trips() {
+    let mut decoded = [0u8; 65536];
+
+    assert_eq!(format_v2_round_trip(&[], false), 4);
+    assert_eq!(format_v2_round_trip(&[0xFF], false), 5);
+    assert_eq!(format_v2_round_trip(&[0x12, 0x34, 0x56], false), 8);
+
+    for len in [1535, 1536, 1537, 4096, 65536] {
+        assert!(format_v2_round_trip(&decoded[..len], false) <= 11);
+    }
+
+    decoded[..513].fill(0xFF);
+    decoded[30000] = 0x01;
+    decoded[40000..50000].fill(0xFF);
+
+    format_v2_round_trip(&decoded, false);
+}
+
+#[test]
+fn adaptive_round_trips() {
+    let mut decoded = [0u8; 65536];
+
+    assert_eq!(format_v2_round_trip(&[], true), 3);
+    assert_eq!(format_v2_round_trip(&[0xFF], true), 3);
+    assert!(format_v2_round_trip(&[0x12, 0x34, 0x56], true) <= 8);
+
+    for len in [1, 2, 3, 7, 8, 1536, 4096, 65536] {
+        assert!(format_v2_round_trip(&decoded[..len], true) <= 9);
+    }
+
+    decoded[..513].fill(0xFF);
+    decoded[30000] = 0x01;
+    decoded[40000..50000].fill(0xFF);
+
+    format_v2_round_trip(&decoded, true);
+
+    let mut state = 0x2545F491u32;
+
+    for (index, byte) in decoded.iter_mut().enumerate() {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+
+        *byte = match index / 1024 % 3 {
+            0 => state as u8,
+            1 if state.is_multiple_of(64) => 0x10,
+            _ => 0x00,
+        };
+    }
+
+    format_v2_round_trip(&decoded[..6144], true);
+}
+
+#[test]
+fn adaptive_adapts() {
+    let mut decoded = [0u8; 4096];
+
+    for index in (0..4096).step_by(64) {
+        decoded[index] = 0x01;
+    }
+
+    let table_len = format_v2_round_trip(&decoded, false);
+    let adaptive_len = format_v2_round_trip(&decoded, true);
+
+    assert!(adaptive_len < table_len);
+}
+
+#[test]
+fn adaptive_flush() {
+    let mut encoder = Encoder::with_format(Format::V2);
+    encoder.set_adaptive();
+
+    let mut encoded = [0u8; 64];
+    let mut encoded_len = 0;
+
+    encoder.flush();
+    encoded_len += encoder.step(&[0x00, 0xF0], &mut encoded).1;
+
+    let mut decoder = ExtendedDecoder::new();
+    let mut decoded = [0u8; 16];
+
+    let (consumed_len, decoded_len, state) = decoder.step(&encoded[..encoded_len], &mut decoded);
+
+    assert_eq!(state, DecoderState::CanConsume);
+    assert_eq!(consumed_len, encoded_len);
+    assert_eq!(&decoded[..decoded_len], &[0x00, 0xF0]);
+
+    encoder.set_consumed_bytes_end();
+    let (_, produced_len, state) = encoder.step(&[0x0F], &mut encoded[encoded_len..]);
+    assert_eq!(state, EncoderState::Terminated);
+
+    let (_, produced_len, state) = decoder.step(
+        &encoded[encoded_len..][..produced_len],
+        &mut decoded[decoded_len..],
+    );
+
+    assert_eq!(
+        state,
+        DecoderState::Terminated {
+            corrupted: false,
+            unaligned: false,
+        }
+    );
+    assert_eq!(&decoded[decoded_len..][..produced_len], &[0x0F]);
+}
+
+#[test]
+fn format_v2_extended_symbol() {
+    let mut encoded = [0u8; 64];
+
+    let mut encoder = Encoder::with_format(Format::V2);
+    encoder.set_consumed_bytes_end();
+
+    let (_, encoded_len, _) = encoder.step_zeroes(1536, &mut encoded);
+
+    // Header, extended symbol for 12288 zeros (Elias-gamma code of 6), termination symbol.
+    assert_eq!(
+        &encoded[..encoded_len],
+        &[0b00000000, 0b00000011, 0b11111111, 0b01001100, 0b00000000, 0b00011111, 0b11111110]
+    );
+}
+
+fn pattern_round_trip(decoded_ref: &[u8], adaptive: bool) -> usize {
+    let mut encoder = Encoder::with_format(Format::V2);
+    encoder.set_consumed_bytes_end();
+    encoder.set_patterns();
+
+    if adaptive {
+        encoder.set_adaptive();
+    }
+
+    let mut encoded = [0u8; 8192];
+    let (_, encoded_len, state) = encoder.step(decoded_ref, &mut encoded);
+    assert_eq!(state, EncoderState::Terminated);
+
+    let mut encoder = Encoder::with_format(Format::V2);
+    encoder.set_patterns();
+
+    if adaptive {
+        encoder.set_adaptive();
+    }
+
+    let mut recoded = [0u8; 8192];
+    let mut recoded_len = 0;
+
+    for &byte in decoded_ref {
+        let (consumed_len, produced_len, state) =
+            encoder.step(&[byte], &mut recoded[recoded_len..]);
+        assert_eq!((consumed_len, state), (1, EncoderState::CanConsume));
+        recoded_len += produced_len;
+    }
+
+    encoder.set_consumed_bytes_end();
+    recoded_len += encoder.step(&[], &mut recoded[recoded_len..]).1;
+
+    assert_eq!(&recoded[..recoded_len], &encoded[..encoded_len]);
+
+    format_v2_decodes(&encoded[..encoded_len], decoded_ref);
+
+    encoded_len
+}
+
+fn format_v2_decodes(encoded: &[u8], decoded_ref: &[u8]) {
+    let mut decoder = ExtendedDecoder::new();
+    let mut decoded = [0u8; 65536];
+
+    let (consumed_len, decoded_len, state) = decoder.step(encoded, &mut decoded);
+
+    assert_eq!(
+        state,
+        DecoderState::Terminated {
+            corrupted: false,
+            unaligned: false,
+        }
+    );
+    assert_eq!(consumed_len, encoded.len());
+    assert_eq!(&decoded[..decoded_len], decoded_ref);
+
+    let mut decoder = ExtendedDecoder::new();
+    let mut decoded_len = 0;
+
+    for &byte in encoded {
+        let (_, produced_len, _) = decoder.step(&[byte], &mut decoded[decoded_len..]);
+        decoded_len += produced_len;
+    }
+
+    assert_eq!(&decoded[..decoded_len], decoded_ref);
+
+    let mut decoder = ExtendedDecoder::new();
+    let mut decoded_len = 0;
+    let mut input = encoded;
+
+    loop {
+        let produced = &mut decoded[decoded_len..];
+        let produced_len = produced.len().min(5);
+        let (consumed_len, extent, state) =
+            decoder.step_extent(input, &mut produced[..produced_len]);
+        input = &input[consumed_len..];
+
+        match extent {
+            Extent::Fill { byte, len } => {
+                assert!(decoded_ref[decoded_len..][..len as usize]
+                    .iter()
+                    .all(|&next| next == byte));
+                decoded_len += len as usize;
+            }
+            Extent::Data(data) => decoded_len += data.len(),
+        }
+
+        if let DecoderState::Terminated { .. } = state {
+            break;
+        }
+    }
+
+    assert_eq!(&decoded[..decoded_len], decoded_ref);
+}
+
+#[test]
+fn pattern_round_trips() {
+    let mut decoded = [0u8; 4096];
+
+    for adaptive in [false, true] {
+        assert_eq!(
+            pattern_round_trip(&[], adaptive),
+            format_v2_round_trip(&[], adaptive)
+        );
+        pattern_round_trip(&[0xAA; 7], adaptive);
+        pattern_round_trip(&[0xAA; 8], adaptive);
+
+        let mut words = [0u8; 256];
+
+        for chunk in words.chunks_exact_mut(4) {
+            chunk.copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+        }
+
+        assert!(pattern_round_trip(&words, adaptive) < 16);
+
+        decoded[..100].fill(0xAA);
+        decoded[100] = 0x01;
+        decoded[101..109].fill(0x5A);
+
+        for chunk in decoded[200..1000].chunks_exact_mut(4) {
+            chunk.copy_from_slice(&[0x20, 0x00, 0x00, 0x00]);
+        }
+
+        decoded[1000..1999].fill(0xFF);
+
+        for chunk in decoded[2000..2400].chunks_exact_mut(4) {
+            chunk.copy_from_slice(&[0x55, 0x55, 0xAA, 0xAA]);
+        }
+
+        decoded[2400..2410].fill(0x80);
+        decoded[4000..].fill(0x3C);
+
+        let pattern_len = pattern_round_trip(&decoded, adaptive);
+        let plain_len = format_v2_round_trip(&decoded, adaptive);
+
+        assert!(pattern_len * 4 < plain_len);
+    }
+}
+
+#[test]
+fn pattern_streaming() {
+    let mut decoded = [0u8; 1024];
+
+    for chunk in decoded[10..1010].chunks_exact_mut(4) {
+        chunk.copy_from_slice(&[0x5A, 0x00, 0xA5, 0xFF]);
+    }
+
+    let mut encoded = [0u8; 64];
+    let encoded_len = {
+        let mut encoder = Encoder::with_format(Format::V2);
+        encoder.set_patterns();
+        encoder.set_consumed_bytes_end();
+        encoder.step(&decoded, &mut encoded).1
+    };
+
+    let mut encoder = Encoder::with_format(Format::V2);
+    encoder.set_patterns();
+
+    let mut recoded = [0u8; 64];
+    let mut recoded_len = 0;
+
+    for chunk in [&decoded[..50], &decoded[50..54], &decoded[54..]] {
+        let (consumed_len, produced_len, state) = encoder.step(chunk, &mut recoded[recoded_len..]);
+        assert_eq!(
+            (consumed_len, state),
+            (chunk.len(), EncoderState::CanConsume)
+        );
+        recoded_len += produced_len;
+    }
+
+    encoder.set_consumed_bytes_end();
+    recoded_len += encoder.step(&[], &mut recoded[recoded_len..]).1;
+
+    assert_eq!(&recoded[..recoded_len], &encoded[..encoded_len]);
+
+    let mut decoder = ExtendedDecoder::new();
+    let mut buffer = [0u8; 1024];
+
+    let (consumed_len, skipped, _) = decoder.skip(&encoded[..encoded_len], 8 * 11 + 3);
+    assert_eq!(skipped, 8 * 11 + 3);
+
+    let (_, produced_len, _) = decoder.step(&encoded[consumed_len..encoded_len], &mut buffer);
+    assert_eq!(produced_len, 1024 - 12);
+    assert_eq!(buffer[0], 0xA5 >> 5);
+}
+
+fn backref_round_trip(decoded_ref: &[u8], adaptive: bool) -> usize {
+    let mut encoder = Encoder::with_format(Format::V2);
+    encoder.set_consumed_bytes_end();
+    encoder.set_backrefs();
+
+    if adaptive {
+        encoder.set_adaptive();
+    }
+
+    let mut encoded = [0u8; 8192];
+    let (_, encoded_len, state) = encoder.step(decoded_ref, &mut encoded);
+    assert_eq!(state, EncoderState::Terminated);
+
+    format_v2_decodes(&encoded[..encoded_len], decoded_ref);
+
+    encoded_len
+}
+
+#[test]
+fn backref_round_trips() {
+    let mut decoded = [0u8; 4096];
+
+    for adaptive in [false, true] {
+        assert_eq!(
+            backref_round_trip(&[], adaptive),
+            format_v2_round_trip(&[], adaptive)
+        );
+        backref_round_trip(&[0x55; 3], adaptive);
+        backref_round_trip(&[0x55; 64], adaptive);
+
+        for (index, chunk) in decoded[..3000].chunks_exact_mut(40).enumerate() {
+            chunk[0] = 0x81;
+            chunk[5] = 0x10;
+            chunk[17] = 0x3C;
+            chunk[30] = if index % 16 == 0 { 0x42 } else { 0x24 };
+        }
+
+        for (index, byte) in decoded[3000..3200].iter_mut().enumerate() {
+            *byte = (index * index / 7) as u8 & (index as u8 >> 3);
+        }
+
+        let backref_len = backref_round_trip(&decoded, adaptive);
+        let plain_len = format_v2_round_trip(&decoded, adaptive);
+
+        assert!(backref_len * 2 < plain_len);
+    }
+}
+
+#[test]
+fn backref_with_patterns_and_flush() {
+    let mut decoded = [0u8; 2048];
+
+    for chunk in decoded[..1024].chunks_exact_mut(32) {
+        chunk[..4].copy_from_slice(&[0x80, 0x01, 0x00, 0x18]);
+        chunk[20..].fill(0x5A);
+    }
+
+    let mut encoder = Encoder::with_format(Format::V2);
+    encoder.set_backrefs();
+    encoder.set_patterns();
+
+    let mut encoded = [0u8; 2048];
+    let mut encoded_len = 0;
+
+    for chunk in decoded.chunks(100) {
+        let (consumed_len, produced_len, _) = encoder.step(chunk, &mut encoded[encoded_len..]);
+        assert_eq!(consumed_len, chunk.len());
+        encoded_len += produced_len;
+
+        encoder.flush();
+        encoded_len += encoder.step(&[], &mut encoded[encoded_len..]).1;
+    }
+
+    encoder.set_consumed_bytes_end();
+    encoded_len += encoder.step(&[], &mut encoded[encoded_len..]).1;
+
+    format_v2_decodes(&encoded[..encoded_len], &decoded);
+}
+
+#[test]
+fn range_decoder_state_size() {
+    // As stated in the README and the documentation of the range decoder.
+    let expected = if core::mem::size_of::<usize>() == 8 {
+        320
+    } else {
+        304
+    };
+
+    assert_eq!(core::mem::size_of::<RangeDecoder>(), expected);
+}
+
+fn range_round_trip(decoded_ref: &[u8]) -> usize {
+    let mut encoded = [0u8; 8192];
+    let encoded_len = encode_range_into_slice(decoded_ref, &mut encoded).unwrap();
+
+    let mut decoded = [0u8; 65536];
+    let decoded_len = decode_range_from_slice(&encoded[..encoded_len], &mut decoded).unwrap();
+    assert_eq!(&decoded[..decoded_len], decoded_ref);
+
+    let mut encoder = RangeEncoder::new();
+    let mut recoded = [0u8; 8192];
+    let mut recoded_len = 0;
+
+    for &byte in decoded_ref {
+        let (consumed_len, produced_len, state) =
+            encoder.step(&[byte], &mut recoded[recoded_len..]);
+        assert_eq!((consumed_len, state), (1, EncoderState::CanConsume));
+        recoded_len += produced_len;
+    }
+
+    encoder.set_consumed_bytes_end();
+
+    loop {
+        let end = (recoded_len + 1).min(recoded.len());
+        let (_, produced_len, state) = encoder.step(&[], &mut recoded[recoded_len..end]);
+        recoded_len += produced_len;
+
+        if state == EncoderState::Terminated {
+            break;
+        }
+    }
+
+    assert_eq!(&recoded[..recoded_len], &encoded[..encoded_len]);
+
+    let mut decoder = RangeDecoder::new();
+    let mut decoded_len = 0;
+
+    for &byte in &encoded[..encoded_len] {
+        let (_, produced_len, _) = decoder.step(&[byte], &mut decoded[decoded_len..]);
+        decoded_len += produced_len;
+    }
+
+    assert_eq!(&decoded[..decoded_len], decoded_ref);
+
+    encoded_len
+}
+
+#[test]
+fn range_round_trips() {
+    let mut decoded = [0u8; 65536];
+
+    assert_eq!(range_round_trip(&[]), 4);
+    assert_eq!(range_round_trip(&[0xFF]), 5);
+    range_round_trip(&[0x12, 0x34, 0x56]);
+
+    for len in [1, 2, 1536, 65536] {
+        assert!(range_round_trip(&decoded[..len]) <= 10);
+    }
+
+    decoded[..513].fill(0xFF);
+    decoded[30000] = 0x01;
+    decoded[40000..50000].fill(0xFF);
+
+    range_round_trip(&decoded);
+
+    let mut state = 0x2545F491u32;
+
+    for byte in &mut decoded[..4096] {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+
+        *byte = state as u8;
+    }
+
+    range_round_trip(&decoded[..4096]);
+}
+
+#[test]
+fn range_ratio() {
+    let mut decoded = [0u8; 16384];
+    let mut state = 0x2545F491u32;
+
+    for byte in &mut decoded[..] {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+
+        if state.is_multiple_of(16) {
+            *byte = 1 << (state / 16 % 8);
+        }
+    }
+
+    let mut encoded = [0u8; 16384];
+    let encoded_len = encode_into_slice(&decoded, &mut encoded).unwrap();
+
+    assert!(range_round_trip(&decoded) * 10 < encoded_len * 9);
+}
+
+#[test]
+fn range_errors() {
+    let mut encoded = [0u8; 64];
+    let encoded_len = encode_range_into_slice(&[0x00, 0x42], &mut encoded).unwrap();
+
+    assert_eq!(
+        decode_range_from_slice(&encoded[..encoded_len - 1], &mut [0u8; 16]),
+        Err(DecodeSliceError::TruncatedInput)
+    );
+
+    assert_eq!(
+        decode_range_from_slice(&encoded[..encoded_len], &mut [0u8; 1]),
+        Err(DecodeSliceError::NeedsMoreSpace)
+    );
+
+    encoded[encoded_len - 1] ^= 0x01;
+
+    assert_eq!(
+        decode_range_from_slice(&encoded[..encoded_len], &mut [0u8; 16]),
+        Err(DecodeSliceError::Corrupted)
+    );
+
+    assert_eq!(
+        encode_range_into_slice(&[0x00, 0x42], &mut [0u8; 2]),
+        Err(EncodeSliceError::NeedsMoreSpace)
+    );
+}
+
+#[test]
+fn format_v2_corrupted() {
+    let expected = DecoderState::Terminated {
+        corrupted: true,
+        unaligned: false,
+    };
+
+    let mut overlong_pattern = [0u8; 16];
+    overlong_pattern[..5]
+        .copy_from_slice(&[0b00000000, 0b00000011, 0b11111111, 0b00001010, 0b10100000]);
+
+    let mut decoder = ExtendedDecoder::new();
+    assert_eq!(decoder.step(&overlong_pattern, &mut []).2, expected);
+
+    overlong_pattern[..5]
+        .copy_from_slice(&[0b10010000, 0b00000000, 0b01111111, 0b11100001, 0b01010100]);
+
+    let mut decoder = ExtendedDecoder::new();
+    assert_eq!(decoder.step(&overlong_pattern, &mut []).2, expected);
+
+    let mut decoder = ExtendedDecoder::new();
+    let distant_backref = [
+        0b10001000, 0b00000000, 0b01111111, 0b11110100, 0b00010000, 0b00000000, 0b11111111,
+        0b11110000,
+    ];
+    assert_eq!(decoder.step(&distant_backref, &mut []).2, expected);
+
+    let mut decoder = ExtendedDecoder::new();
+    let mut overlong = [0u8; 16];
+    overlong[..4].copy_from_slice(&[0b00000000, 0b00000011, 0b11111111, 0b01000000]);
+    assert_eq!(decoder.step(&overlong, &mut []).2, expected);
+}
+
+struct SymmetricCode;
+
+impl Code for SymmetricCode {
+    const PAYLOAD_BITS: [[u8; 12]; 2] = [
+        [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+        [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+    ];
+}
+
+fn code_round_trip<C: Code>(format: Format, decoded_ref: &[u8]) -> usize {
+    let mut encoder = Encoder::<C>::with_code(format);
+    encoder.set_consumed_bytes_end();
+
+    let mut encoded = [0u8; 1024];
+    let (_, encoded_len, state) = encoder.step(decoded_ref, &mut encoded);
+    assert_eq!(state, EncoderState::Terminated);
+
+    let mut decoder = Decoder::<C>::with_code();
+    let mut extended_decoder = ExtendedDecoder::<C>::with_code();
+    let mut decoded = [0u8; 65536];
+    let mut decoded_len = 0;
+
+    for &byte in &encoded[..encoded_len] {
+        let (_, produced_len, _) = match format {
+            Format::V1 => decoder.step(&[byte], &mut decoded[decoded_len..]),
+            Format::V2 => extended_decoder.step(&[byte], &mut decoded[decoded_len..]),
+        };
+        decoded_len += produced_len;
+    }
+
+    assert_eq!(&decoded[..decoded_len], decoded_ref);
+
+    encoded_len
+}
+
+#[test]
+fn code_default_matches_tables() {
+    let mut decoded = [0u8; 4096];
+    decoded[100..120].fill(0xFF);
+    decoded[1000..3000].fill(0xFF);
+    decoded[3500] = 0x5A;
+
+    let mut encoded = [0u8; 256];
+    let encoded_len = encode_into_slice(&decoded, &mut encoded).unwrap();
+
+    assert_eq!(
+        code_round_trip::<DefaultCode>(Format::V1, &decoded),
+        encoded_len
+    );
+}
+
+#[test]
+fn code_custom_tables() {
+    let mut decoded = [0u8; 4096];
+
+    for chunk in decoded.chunks_mut(64) {
+        chunk[..20].fill(0xFF);
+    }
+
+    decoded[2000..3000].fill(0xFF);
+
+    for format in [Format::V1, Format::V2] {
+        let default_len = code_round_trip::<DefaultCode>(format, &decoded);
+        let custom_len = code_round_trip::<SymmetricCode>(format, &decoded);
+
+        assert!(custom_len < default_len);
+    }
+}
+
+#[test]
+fn decode_step_extent() {
+    let mut decoded = [0u8; 35];
+    decoded[32..].copy_from_slice(&[0xFF, 0xFF, 0x80]);
+
+    let mut encoded = [0u8; 16];
+    let encoded_len = encode_into_slice(&decoded, &mut encoded).unwrap();
+    let encoded = &encoded[..encoded_len];
+
+    let mut decoder = Decoder::new();
+    let mut buffer = [0u8; 32];
+
+    assert_eq!(
+        decoder.step_extent(encoded, &mut buffer),
+        (
+            3,
+            Extent::Fill {
+                byte: 0x00,
+                len: 32
+            },
+            DecoderState::CanProduce
+        )
+    );
+
+    assert_eq!(
+        decoder.step_extent(&encoded[3..], &mut buffer),
+        (
+            2,
+            Extent::Fill { byte: 0xFF, len: 2 },
+            DecoderState::CanProduce
+        )
+    );
+
+    assert_eq!(
+        decoder.step_extent(&encoded[5..], &mut buffer),
+        (
+            encoded_len - 5,
+            Extent::Data(&[0x80]),
+            DecoderState::Terminated {
+                corrupted: false,
+                unaligned: false
+            }
+        )
+    );
+}
+
+#[test]
+fn decode_skip() {
+    let mut decoded = [0u8; 4096];
+    decoded[..4].copy_from_slice(&[0x12, 0x34, 0x56, 0x78]);
+    decoded[4000] = 0xA5;
+
+    let mut encoded = [0u8; 64];
+    let encoded_len = encode_into_slice(&decoded, &mut encoded).unwrap();
+    let encoded = &encoded[..encoded_len];
+
+    let mut decoder = Decoder::new();
+    let mut buffer = [0u8; 4096];
+
+    assert_eq!(decoder.skip(encoded, 0), (0, 0, DecoderState::CanProduce));
+
+    let (consumed_len, produced_len, state) = decoder.step(encoded, &mut buffer[..1]);
+    assert_eq!((produced_len, state), (1, DecoderState::CanProduce));
+    assert_eq!(buffer[0], 0x12);
+
+    let encoded = &encoded[consumed_len..];
+    let (consumed_len, skipped, state) = decoder.skip(encoded, 12);
+    assert_eq!((skipped, state), (12, DecoderState::CanProduce));
+
+    let encoded = &encoded[consumed_len..];
+    let (consumed_len, produced_len, state) = decoder.step(encoded, &mut buffer[..2]);
+    assert_eq!((produced_len, state), (2, DecoderState::CanProduce));
+    assert_eq!(&buffer[..2], &[0x67, 0x80]);
+
+    let encoded = &encoded[consumed_len..];
+    let (consumed_len, skipped, state) = decoder.skip(encoded, 8 * 3990);
+    assert_eq!((skipped, state), (8 * 3990, DecoderState::CanProduce));
+
+    let encoded = &encoded[consumed_len..];
+    let (_, produced_len, state) = decoder.step(encoded, &mut buffer);
+    assert_eq!(
+        state,
+        DecoderState::Terminated {
+            corrupted: false,
+            unaligned: true,
+        }
+    );
+    assert_eq!(produced_len, 4096 - 3994 - 1);
+    assert_eq!(
+        buffer[..produced_len].iter().filter(|&&b| b != 0).count(),
+        2
+    );
+    assert_eq!(decoder.partial_output_byte(), Some((0x0, 4)));
+}
+
+#[test]
+fn decode_skip_past_end() {
+    let mut encoded = [0u8; 64];
+    let encoded_len = encode_into_slice(&[0x00, 0xFF, 0x00], &mut encoded).unwrap();
+
+    let mut decoder = Decoder::new();
+
+    assert_eq!(
+        decoder.skip(&encoded[..encoded_len], 100),
+        (
+            encoded_len,
+            24,
+            DecoderState::Terminated {
+                corrupted: false,
+                unaligned: false,
+            }
+        )
+    );
+}
+
+#[test]
+fn extent_reader_merges_fills() {
+    let mut decoded = [0u8; 4000];
+    decoded[3000..3003].copy_from_slice(&[0x0F, 0xFF, 0xFF]);
+    decoded[3003..].fill(0xFF);
+    decoded[3999] = 0x12;
+
+    let mut encoded = [0u8; 64];
+    let encoded_len = encode_into_slice(&decoded, &mut encoded).unwrap();
+
+    let mut reader = ExtentReader::new(&encoded[..encoded_len]);
+    let mut buffer = [0u8; 8];
+
+    assert_eq!(
+        reader.next_extent(&mut buffer),
+        Some(Ok((
+            0,
+            Extent::Fill {
+                byte: 0x00,
+                len: 3000
+            }
+        )))
+    );
+
+    assert_eq!(
+        reader.next_extent(&mut buffer),
+        Some(Ok((3000, Extent::Data(&[0x0F]))))
+    );
+
+    assert_eq!(
+        reader.next_extent(&mut buffer),
+        Some(Ok((
+            3001,
+            Extent::Fill {
+                byte: 0xFF,
+                len: 998
+            }
+        )))
+    );
+
+    assert_eq!(
+        reader.next_extent(&mut buffer),
+        Some(Ok((3999, Extent::Data(&[0x12]))))
+    );
+
+    assert_eq!(reader.next_extent(&mut buffer), None);
+}
+
+#[test]
+fn extent_reader_adjacent_fills() {
+    let mut encoded = [0u8; 16];
+    let encoded_len = encode_into_slice(&[0x00, 0x00, 0xFF, 0xFF], &mut encoded).unwrap();
+
+    let mut reader = ExtentReader::new(&encoded[..encoded_len]);
+    let mut buffer = [0u8; 8];
+
+    assert_eq!(
+        reader.next_extent(&mut buffer),
+        Some(Ok((0, Extent::Fill { byte: 0x00, len: 2 })))
+    );
+
+    assert_eq!(
+        reader.next_extent(&mut buffer),
+        Some(Ok((2, Extent::Fill { byte: 0xFF, len: 2 })))
+    );
+
+    assert_eq!(reader.next_extent(&mut buffer), None);
+}
+
+#[test]
+fn extent_reader_errors() {
+    let mut buffer = [0u8; 8];
+
+    let mut reader = ExtentReader::new(&[0b10001000, 0b00000000, 0b01111111, 0b11111000]);
+    assert_eq!(
+        reader.next_extent(&mut buffer),
+        Some(Err(DecodeSliceError::Unaligned))
+    );
+    assert_eq!(reader.next_extent(&mut buffer), None);
+
+    let mut reader = ExtentReader::new(&[0b00111000]);
+    assert_eq!(
+        reader.next_extent(&mut buffer),
+        Some(Err(DecodeSliceError::TruncatedInput))
+    );
+}
+
+#[test]
+fn encode_step_zeroes() {
+    let mut decoded = [0u8; 3000];
+    decoded[0] = 0b10110000;
+    decoded[2999] = 0b00000001;
+
+    let mut expected = [0u8; 64];
+    let expected_len = encode_into_slice(&decoded, &mut expected).unwrap();
+
+    let mut encoder = Encoder::new();
+    let mut encoded = [0u8; 64];
+    let mut encoded_len = 0;
+
+    let (consumed_len, produced_len, state) = encoder.step(&decoded[..1], &mut encoded);
+    assert_eq!((consumed_len, state), (1, EncoderState::CanConsume));
+    encoded_len += produced_len;
+
+    let (consumed_len, produced_len, state) =
+        encoder.step_zeroes(2998, &mut encoded[encoded_len..]);
+    assert_eq!((consumed_len, state), (2998, EncoderState::CanConsume));
+    encoded_len += produced_len;
+
+    encoder.set_consumed_bytes_end();
+
+    let (consumed_len, produced_len, state) =
+        encoder.step(&decoded[2999..], &mut encoded[encoded_len..]);
+    assert_eq!((consumed_len, state), (1, EncoderState::Terminated));
+    encoded_len += produced_len;
+
+    assert_eq!(&encoded[..encoded_len], &expected[..expected_len]);
+}
+
+#[test]
+fn encode_bits_unaligned() {
+    let mut encoded = [0u8; 32];
+
+    let encoded_len = encode_bits_into_slice(&[0b01110000], 4, &mut encoded).unwrap();
+    assert_eq!(
+        &encoded[..encoded_len],
+        &[0b10001000, 0b00000000, 0b01111111, 0b11111000]
+    );
+
+    let encoded_len = encode_bits_into_slice(&[0, 0, 0, 0b00001111], 29, &mut encoded).unwrap();
+    assert_eq!(
+        &encoded[..encoded_len],
+        &[0b00011101, 0b10000000, 0b00000111, 0b11111111, 0b10000000]
+    );
+
+    let encoded_len = encode_bits_into_slice(&[0b10101010, 0b11111111], 8, &mut encoded).unwrap();
+    assert_eq!(
+        &encoded[..encoded_len],
+        &[
+            0b00000000, 0b00001111, 0b11111110, 0b11011011, 0b01100000, 0b00000000, 0b11111111,
+            0b11110000,
+        ]
+    );
+}
+
+#[test]
+fn encode_partial_input_byte_round_trip() {
+    let mut encoder = Encoder::new();
+    let mut encoded = [0u8; 32];
+
+    encoder.set_partial_input_byte(0b1111011, 7);
+
+    let (consumed_len, encoded_len, state) = encoder.step(&[0xFF, 0x00], &mut encoded);
+    assert_eq!((consumed_len, state), (2, EncoderState::Terminated));
+
+    let mut decoder = Decoder::new();
+    let mut decoded = [0u8; 32];
+
+    let (_, decoded_len, state) = decoder.step(&encoded[..encoded_len], &mut decoded);
+
+    assert_eq!(
+        state,
+        DecoderState::Terminated {
+            corrupted: false,
+            unaligned: true
+        }
+    );
+    assert_eq!(&decoded[..decoded_len], &[0xFF, 0x00]);
+    assert_eq!(decoder.partial_output_byte(), Some((0b1111011, 7)));
+}
+
+#[test]
+fn encode_flush() {
+    let mut chunks = [[0u8; 2000]; 3];
+    chunks[0][1997..].copy_from_slice(&[0x00, 0x00, 0x0F]);
+    chunks[1][..2].copy_from_slice(&[0xFF, 0xF0]);
+    chunks[2][1999] = 0x80;
+
+    let mut encoder = Encoder::new();
+    let mut encoded = [0u8; 128];
+    let mut encoded_len = 0;
+
+    let mut decoder = Decoder::new();
+    let mut decoded = [0u8; 6000];
+    let mut decoded_len = 0;
+    let mut decoded_end = 0;
+
+    for chunk in &chunks {
+        encoder.flush();
+
+        let (consumed_len, flushed_len, state) = encoder.step(chunk, &mut encoded[encoded_len..]);
+        assert_eq!((consumed_len, state), (2000, EncoderState::CanConsume));
+
+        let flushed = &encoded[encoded_len..][..flushed_len];
+        let (_, produced_len, state) = decoder.step(flushed, &mut decoded[decoded_len..]);
+        assert_eq!(state, DecoderState::CanConsume);
+
+        encoded_len += flushed_len;
+        decoded_len += produced_len;
+        decoded_end += chunk.len();
+
+        assert_eq!(decoded_len, decoded_end);
+        assert_eq!(
+            &decoded[..decoded_len],
+            &chunks.as_flattened()[..decoded_end]
+        );
+    }
+
+    encoder.set_consumed_bytes_end();
+
+    let (_, produced_len, state) = encoder.step(&[], &mut encoded[encoded_len..]);
+    assert_eq!(state, EncoderState::Terminated);
+    encoded_len += produced_len;
+
+    let mut decoded = [0u8; 6000];
+    assert_eq!(
+        decode_from_slice(&encoded[..encoded_len], &mut decoded),
+        Ok(6000)
+    );
+    assert_eq!(&decoded, chunks.as_flattened());
+}
+
+#[test]
+fn encode_flush_small_output() {
+    let decoded_ref = [0x0F, 0xF0, 0x00, 0x00, 0x81, 0xFF, 0x3C];
+
+    for output_len in 1..=3 {
+        let mut encoder = Encoder::new();
+        let mut encoded = [0u8; 256];
+        let mut encoded_len = 0;
+
+        let mut input = &decoded_ref[..];
+
+        while !input.is_empty() {
+            encoder.flush();
+
+            let end = encoded_len + output_len;
+            let (consumed_len, produced_len, _) =
+                encoder.step(&input[..1], &mut encoded[encoded_len..end]);
+            input = &input[consumed_len..];
+            encoded_len += produced_len;
+        }
+
+        encoder.set_consumed_bytes_end();
+
+        loop {
+            let end = encoded_len + output_len;
+            let (_, produced_len, state) = encoder.step(&[], &mut encoded[encoded_len..end]);
+            encoded_len += produced_len;
+
+            if state == EncoderState::Terminated {
+                break;
+            }
+        }
+
+        let mut decoded = [0u8; 16];
+        assert_eq!(
+            decode_from_slice(&encoded[..encoded_len], &mut decoded),
+            Ok(decoded_ref.len())
+        );
+        assert_eq!(&decoded[..decoded_ref.len()], &decoded_ref);
+    }
+
+    let mut encoder = Encoder::new();
+    let mut encoded = [0u8; 64];
+
+    encoder.flush();
+
+    let (consumed_len, encoded_len, state) = encoder.step(&[0x0F], &mut encoded[..2]);
+    assert_eq!((consumed_len, state), (1, EncoderState::CanProduce));
+
+    encoder.set_consumed_bytes_end();
+
+    let (consumed_len, produced_len, state) = encoder.step(&[0xF0], &mut encoded[encoded_len..]);
+    assert_eq!((consumed_len, state), (1, EncoderState::Terminated));
+
+    let mut decoded = [0u8; 2];
+    assert_eq!(
+        decode_from_slice(&encoded[..encoded_len + produced_len], &mut decoded),
+        Ok(2)
+    );
+    assert_eq!(decoded, [0x0F, 0xF0]);
+}
+
+#[test]
+fn encode_flush_partial() {
+    let mut encoder = Encoder::new();
+    let mut encoded = [0u8; 64];
+
+    encoder.flush();
+    encoder.set_partial_input_byte(0b1, 1);
+
+    let (_, encoded_len, state) = encoder.step(&[0x00], &mut encoded);
+    assert_eq!(state, EncoderState::Terminated);
+
+    let mut decoder = Decoder::new();
+    let mut decoded = [0u8; 16];
+
+    let (_, decoded_len, state) = decoder.step(&encoded[..encoded_len], &mut decoded);
+
+    assert_eq!(
+        state,
+        DecoderState::Terminated {
+            corrupted: false,
+            unaligned: true,
+        }
+    );
+    assert_eq!(&decoded[..decoded_len], &[0x00]);
+    assert_eq!(decoder.partial_output_byte(), Some((0b1, 1)));
+}
+
+fn encode_resume_round_trip(head: &[u8], tail: &[u8]) {
+    let mut decoded_ref = [0u8; 4096];
+    decoded_ref[..head.len()].copy_from_slice(head);
+    decoded_ref[head.len()..][..tail.len()].copy_from_slice(tail);
+
+    let mut encoded_ref = [0u8; 64];
+    let encoded_ref_len =
+        encode_into_slice(&decoded_ref[..head.len() + tail.len()], &mut encoded_ref).unwrap();
+
+    let mut encoded = [0u8; 64];
+    let head_len = encode_into_slice(head, &mut encoded).unwrap();
+
+    let (mut encoder, kept_len) = Encoder::resume_from(&encoded[..head_len]).unwrap();
+    encoder.set_consumed_bytes_end();
+
+    let (_, produced_len, state) = encoder.step(tail, &mut encoded[kept_len..]);
+
+    assert_eq!(state, EncoderState::Terminated);
+    assert_eq!(
+        &encoded[..kept_len + produced_len],
+        &encoded_ref[..encoded_ref_len]
+    );
+}
+
+#[test]
+fn encode_resume_from() {
+    encode_resume_round_trip(&[], &[]);
+    encode_resume_round_trip(&[], &[0b10101010]);
+    encode_resume_round_trip(&[0x00, 0x0F], &[0xFF, 0x00]);
+    encode_resume_round_trip(&[0x00, 0x0F], &[0x00, 0xF0]);
+    encode_resume_round_trip(&[0xFF], &[0xFF, 0x01]);
+    encode_resume_round_trip(&[0x00; 1536], &[0x00; 1536]);
+    encode_resume_round_trip(&[0x00; 1536], &[0x80]);
+}
+
+#[test]
+fn encode_resume_from_unaligned() {
+    let mut encoded = [0u8; 64];
+    let encoded_len = encode_bits_into_slice(&[0x12, 0b01100000], 11, &mut encoded).unwrap();
+
+    let (mut encoder, kept_len) = Encoder::resume_from(&encoded[..encoded_len]).unwrap();
+    encoder.set_partial_input_byte(0b10110, 5);
+
+    let (_, produced_len, state) = encoder.step(&[0x00], &mut encoded[kept_len..]);
+    assert_eq!(state, EncoderState::Terminated);
+
+    let mut expected = [0u8; 64];
+    let expected_len = encode_into_slice(&[0x12, 0b01100000, 0b00010110], &mut expected).unwrap();
+
+    assert_eq!(
+        &encoded[..kept_len + produced_len],
+        &expected[..expected_len]
+    );
+}
+
+#[test]
+fn encode_resume_from_errors() {
+    assert_eq!(
+        Encoder::resume_from(&[0b00111000]).err(),
+        Some(DecodeSliceError::TruncatedInput)
+    );
+
+    assert_eq!(
+        Encoder::resume_from(&[0b00100100, 0b00000000, 0b00111111, 0b11111111]).err(),
+        Some(DecodeSliceError::Corrupted)
+    );
+}
+
+fn concat_round_trip(first: &[u8], second: &[u8]) {
+    let mut decoded_ref = [0u8; 8192];
+    decoded_ref[..first.len()].copy_from_slice(first);
+    decoded_ref[first.len()..][..second.len()].copy_from_slice(second);
+
+    let mut encoded_ref = [0u8; 64];
+    let encoded_ref_len =
+        encode_into_slice(&decoded_ref[..first.len() + second.len()], &mut encoded_ref).unwrap();
+
+    let mut encoded_first = [0u8; 64];
+    let first_len = encode_into_slice(first, &mut encoded_first).unwrap();
+
+    let mut encoded_second = [0u8; 64];
+    let second_len = encode_into_slice(second, &mut encoded_second).unwrap();
+
+    let mut encoded = [0u8; 64];
+    let encoded_len = concat(
+        &encoded_first[..first_len],
+        &encoded_second[..second_len],
+        &mut encoded,
+    )
+    .unwrap();
+
+    assert_eq!(&encoded[..encoded_len], &encoded_ref[..encoded_ref_len]);
+}
+
+#[test]
+fn concat_simple() {
+    concat_round_trip(&[], &[]);
+    concat_round_trip(&[], &[0x12, 0x34]);
+    concat_round_trip(&[0x12, 0x34], &[]);
+    concat_round_trip(&[0x00, 0x0F], &[0xFF, 0x00]);
+    concat_round_trip(&[0x00, 0x0F], &[0x00, 0xF0]);
+    concat_round_trip(&[0xFF], &[0xFF, 0x01]);
+    concat_round_trip(&[0xFF; 600], &[0xFF; 600]);
+    concat_round_trip(&[0x00; 1536], &[0x00; 1536]);
+    concat_round_trip(&[0x00; 4096], &[0x00; 4096]);
+    concat_round_trip(&[0x00; 1536], &[0x80]);
+}
+
+#[test]
+fn concat_unaligned() {
+    let mut first = [0u8; 64];
+    let first_len = encode_bits_into_slice(&[0x12, 0b01100000], 11, &mut first).unwrap();
+
+    let mut second = [0u8; 64];
+    let second_len = encode_bits_into_slice(&[0b10110000], 5, &mut second).unwrap();
+
+    let mut encoded = [0u8; 64];
+    let encoded_len = concat(&first[..first_len], &second[..second_len], &mut encoded).unwrap();
+
+    let mut expected = [0u8; 64];
+    let expected_len = encode_into_slice(&[0x12, 0b01110110], &mut expected).unwrap();
+
+    assert_eq!(&encoded[..encoded_len], &expected[..expected_len]);
+}
+
+#[test]
+fn concat_errors() {
+    let mut encoded = [0u8; 64];
+    let encoded_len = encode_into_slice(&[0x12, 0x34], &mut encoded).unwrap();
+    let encoded = &encoded[..encoded_len];
+
+    assert_eq!(
+        concat(&[0b00111000], encoded, &mut [0u8; 64]),
+        Err(DecodeSliceError::TruncatedInput)
+    );
+
+    assert_eq!(
+        concat(
+            encoded,
+            &[0b00100100, 0b00000000, 0b00111111, 0b11111111],
+            &mut [0u8; 64]
+        ),
+        Err(DecodeSliceError::Corrupted)
+    );
+
+    assert_eq!(
+        concat(encoded, encoded, &mut [0u8; 4]),
+        Err(DecodeSliceError::NeedsMoreSpace)
+    );
+}
+
+#[test]
+fn run_writer_long_run() {
+    let mut encoder = Encoder::new();
+    encoder.queued_bits = usize::MAX - 10;
+
+    let mut output = [0u8; 8];
+    let run = Run {
+        bit: false,
+        len: 20,
+    };
+
+    RunWriter::resume(encoder, &mut output, 0)
+        .push(run)
+        .unwrap();
+
+    // Continuation symbol produced to make room for the remainder of the run.
+    assert_eq!(output, [0x00, 0x0F, 0xFD, 0x00, 0x00, 0x00, 0x00, 0x00]);
+}
+
+fn slice_compressed_round_trip(decoded: &[u8], bit_range: Range<u64>) {
+    let mut encoded = [0u8; 64];
+    let encoded_len = encode_into_slice(decoded, &mut encoded).unwrap();
+
+    let mut sliced = [0u8; 64];
+    let sliced_len =
+        slice_compressed(&encoded[..encoded_len], bit_range.clone(), &mut sliced).unwrap();
+
+    let mut expected = [0u8; 4096];
+    let mut expected_bits = 0;
+
+    for bit_pos in bit_range {
+        if decoded[bit_pos as usize / 8] & (0x80 >> (bit_pos % 8)) != 0 {
+            expected[expected_bits / 8] |= 0x80 >> (expected_bits % 8);
+        }
+
+        expected_bits += 1;
+    }
+
+    let mut expected_encoded = [0u8; 64];
+    let expected_encoded_len =
+        encode_bits_into_slice(&expected, expected_bits as u64, &mut expected_encoded).unwrap();
+
+    assert_eq!(
+        &sliced[..sliced_len],
+        &expected_encoded[..expected_encoded_len]
+    );
+}
+
+#[test]
+fn slice_compressed_simple() {
+    let mut decoded = [0u8; 3072];
+    decoded[1] = 0x0F;
+    decoded[2] = 0xFF;
+    decoded[1500..1520].fill(0xFF);
+    decoded[3071] = 0x81;
+
+    slice_compressed_round_trip(&decoded, 0..0);
+    slice_compressed_round_trip(&decoded, 0..24576);
+    slice_compressed_round_trip(&decoded, 0..8);
+    slice_compressed_round_trip(&decoded, 12..20);
+    slice_compressed_round_trip(&decoded, 13..27);
+    slice_compressed_round_trip(&decoded, 20..12000);
+    slice_compressed_round_trip(&decoded, 11990..12170);
+    slice_compressed_round_trip(&decoded, 12100..24570);
+    slice_compressed_round_trip(&decoded, 24568..24576);
+}
+
+#[test]
+fn slice_compressed_past_end() {
+    let mut encoded = [0u8; 64];
+    let encoded_len = encode_into_slice(&[0x00, 0xF0], &mut encoded).unwrap();
+
+    let mut sliced = [0u8; 64];
+    let sliced_len = slice_compressed(&encoded[..encoded_len], 8..64, &mut sliced).unwrap();
+
+    let mut expected = [0u8; 64];
+    let expected_len = encode_into_slice(&[0xF0], &mut expected).unwrap();
+
+    assert_eq!(&sliced[..sliced_len], &expected[..expected_len]);
+}
+
+#[test]
+fn rank_select() {
+    let mut decoded = [0u8; 3072];
+    decoded[1] = 0x0F;
+    decoded[2] = 0xFF;
+    decoded[1500..1520].fill(0xFF);
+    decoded[3071] = 0x81;
+
+    let mut encoded = [0u8; 64];
+    let encoded_len = encode_into_slice(&decoded, &mut encoded).unwrap();
+    let encoded = &encoded[..encoded_len];
+
+    let ones = decoded.iter().map(|byte| byte.count_ones() as u64).sum();
+    assert_eq!(count_ones(encoded), Ok(ones));
+
+    let mut count = 0;
+
+    for bit_pos in 0..decoded.len() as u64 * 8 {
+        assert_eq!(rank(encoded, bit_pos), Ok(count));
+
+        if decoded[bit_pos as usize / 8] & (0x80 >> (bit_pos % 8)) != 0 {
+            assert_eq!(select(encoded, count), Ok(Some(bit_pos)));
+            count += 1;
+        }
+    }
+
+    assert_eq!(rank(encoded, u64::MAX), Ok(ones));
+    assert_eq!(select(encoded, ones), Ok(None));
+}
+
+#[test]
+fn rank_select_errors() {
+    let truncated = [0b00111000];
+
+    assert_eq!(
+        count_ones(&truncated),
+        Err(DecodeSliceError::TruncatedInput)
+    );
+    assert_eq!(rank(&truncated, 1), Err(DecodeSliceError::TruncatedInput));
+    assert_eq!(rank(&truncated, 0), Ok(0));
+    assert_eq!(select(&truncated, 0), Err(DecodeSliceError::TruncatedInput));
+}
+
+fn combine_compressed_round_trip(first: &[u8], second: &[u8]) {
+    let len = first.len().max(second.len());
+
+    let mut encoded_first = [0u8; 64];
+    let first_len = encode_into_slice(first, &mut encoded_first).unwrap();
+
+    let mut encoded_second = [0u8; 64];
+    let second_len = encode_into_slice(second, &mut encoded_second).unwrap();
+
+    for op in [BitOp::And, BitOp::Or, BitOp::Xor, BitOp::AndNot] {
+        let mut expected = [0u8; 4096];
+
+        for (i, byte) in expected[..len].iter_mut().enumerate() {
+            let a = first.get(i).copied().unwrap_or(0);
+            let b = second.get(i).copied().unwrap_or(0);
+
+            *byte = match op {
+                BitOp::And => a & b,
+                BitOp::Or => a | b,
+                BitOp::Xor => a ^ b,
+                BitOp::AndNot => a & !b,
+            };
+        }
+
+        let mut expected_encoded = [0u8; 64];
+        let expected_len = encode_into_slice(&expected[..len], &mut expected_encoded).unwrap();
+
+        let mut encoded = [0u8; 64];
+        let encoded_len = combine_compressed(
+            &encoded_first[..first_len],
+            &encoded_second[..second_len],
+            op,
+            &mut encoded,
+        )
+        .unwrap();
+
+        assert_eq!(
+            &encoded[..encoded_len],
+            &expected_encoded[..expected_len],
+            "{op:?}"
+        );
+    }
+}
+
+#[test]
+fn combine_compressed_simple() {
+    let mut first = [0u8; 2048];
+    first[1] = 0x0F;
+    first[500..700].fill(0xFF);
+    first[2047] = 0x81;
+
+    let mut second = [0u8; 2048];
+    second[1] = 0x3C;
+    second[600..800].fill(0xFF);
+    second[1000] = 0x10;
+
+    combine_compressed_round_trip(&[], &[]);
+    combine_compressed_round_trip(&first, &second);
+    combine_compressed_round_trip(&first, &first);
+    combine_compressed_round_trip(&first, &second[..1000]);
+    combine_compressed_round_trip(&first[..3], &second);
+    combine_compressed_round_trip(&[0xFF; 4], &[]);
+}
+
+#[test]
+fn combine_compressed_errors() {
+    let mut encoded = [0u8; 64];
+    let encoded_len = encode_into_slice(&[0x12, 0x34], &mut encoded).unwrap();
+    let encoded = &encoded[..encoded_len];
+
+    assert_eq!(
+        combine_compressed(encoded, &[0b00111000], BitOp::Or, &mut [0u8; 64]),
+        Err(DecodeSliceError::TruncatedInput)
+    );
+
+    assert_eq!(
+        combine_compressed(encoded, encoded, BitOp::Or, &mut [0u8; 2]),
+        Err(DecodeSliceError::NeedsMoreSpace)
+    );
+}
+
+#[test]
+fn compare_equal() {
+    let mut decoded = [0u8; 3072];
+    decoded[1] = 0x0F;
+    decoded[2] = 0xFF;
+    decoded[3071] = 0x81;
+
+    let mut encoded = [0u8; 64];
+    let encoded_len = encode_into_slice(&decoded, &mut encoded).unwrap();
+    let encoded = &encoded[..encoded_len];
+
+    let mut first = [0u8; 64];
+    let mut second = [0u8; 64];
+
+    let first_len = slice_compressed(encoded, 0..12000, &mut first).unwrap();
+    let second_len = slice_compressed(encoded, 12000..24576, &mut second).unwrap();
+
+    let mut alternative = [0u8; 64];
+    let alternative_len =
+        concat(&first[..first_len], &second[..second_len], &mut alternative).unwrap();
+
+    let mut canonical = [0u8; 64];
+    let canonical_len = encode_into_slice(&[0x60], &mut canonical).unwrap();
+
+    // Encoding of the same data splitting its run of ones with a mode change symbol, which the
+    // encoder would not produce itself.
+    let noncanonical = [
+        0b10100000, 0b00000001, 0b11111111, 0b11010110, 0b00000000, 0b00001111, 0b11111111,
+    ];
+
+    assert_ne!(&canonical[..canonical_len], &noncanonical);
+
+    assert_eq!(compare(encoded, &alternative[..alternative_len]), Ok(true));
+    assert_eq!(
+        compare(&canonical[..canonical_len], &noncanonical),
+        Ok(true)
+    );
+    assert_eq!(diff(encoded, encoded).next(), None);
+}
+
+#[test]
+fn compare_differing() {
+    let mut first = [0u8; 3072];
+    first[1] = 0x0F;
+    first[2000..2002].fill(0xFF);
+
+    let mut second = [0u8; 3072];
+    second[1] = 0x3C;
+    second[2001..2003].fill(0xFF);
+
+    let mut encoded_first = [0u8; 64];
+    let first_len = encode_into_slice(&first, &mut encoded_first).unwrap();
+    let encoded_first = &encoded_first[..first_len];
+
+    let mut encoded_second = [0u8; 64];
+    let second_len = encode_into_slice(&second, &mut encoded_second).unwrap();
+    let encoded_second = &encoded_second[..second_len];
+
+    let mut encoded_shorter = [0u8; 64];
+    let shorter_len = encode_into_slice(&first[..2001], &mut encoded_shorter).unwrap();
+    let encoded_shorter = &encoded_shorter[..shorter_len];
+
+    assert_eq!(compare(encoded_first, encoded_second), Ok(false));
+    assert_eq!(compare(encoded_first, encoded_shorter), Ok(false));
+
+    let mut ranges = diff(encoded_first, encoded_second);
+    assert_eq!(ranges.next(), Some(Ok(10..12)));
+    assert_eq!(ranges.next(), Some(Ok(14..16)));
+    assert_eq!(ranges.next(), Some(Ok(16000..16008)));
+    assert_eq!(ranges.next(), Some(Ok(16016..16024)));
+    assert_eq!(ranges.next(), None);
+
+    let mut ranges = diff(encoded_first, encoded_shorter);
+    assert_eq!(ranges.next(), Some(Ok(16008..24576)));
+    assert_eq!(ranges.next(), None);
+
+    let mut ranges = diff(encoded_first, &[0b00111000]);
+    assert_eq!(ranges.next(), Some(Err(DecodeSliceError::TruncatedInput)));
+    assert_eq!(ranges.next(), None);
+}
+
+#[test]
+fn verify_against_matching() {
+    let mut decoded = [0u8; 3072];
+    decoded[1] = 0x0F;
+    decoded[2] = 0xFF;
+    decoded[1500..1520].fill(0xFF);
+    decoded[3000..3050].copy_from_slice(&[0x5A; 50]);
+
+    let mut encoded = [0u8; 256];
+    let encoded_len = encode_into_slice(&decoded, &mut encoded).unwrap();
+    let encoded = &encoded[..encoded_len];
+
+    assert_eq!(verify_against(encoded, &decoded, None), Ok(None));
+
+    let mut verifier = Verifier::new(encoded);
+
+    for chunk in decoded.chunks(7) {
+        verifier.verify::<4>(chunk, None).unwrap();
+    }
+
+    assert_eq!(verifier.finish(), Ok(3072));
+}
+
+#[test]
+fn verify_against_mismatch() {
+    let mut decoded = [0u8; 3072];
+    decoded[1] = 0x0F;
+    decoded[1500..1520].fill(0xFF);
+    decoded[3000..3050].copy_from_slice(&[0x5A; 50]);
+
+    let mut encoded = [0u8; 256];
+    let encoded_len = encode_into_slice(&decoded, &mut encoded).unwrap();
+    let encoded = &encoded[..encoded_len];
+
+    let mut data = decoded;
+    data[1510] = 0xFE;
+    data[3020] = 0x5B;
+
+    let mut mask = [0u8; 3072];
+
+    assert_eq!(verify_against(encoded, &data, None), Ok(Some(1510)));
+    assert_eq!(verify_against(encoded, &data, Some(&mask)), Ok(Some(1510)));
+
+    mask[1510] = 0x01;
+    assert_eq!(verify_against(encoded, &data, Some(&mask)), Ok(Some(3020)));
+
+    mask[3020] = 0x01;
+    assert_eq!(verify_against(encoded, &data, Some(&mask)), Ok(None));
+
+    assert_eq!(
+        verify_against(encoded, &decoded[..3000], None),
+        Ok(Some(3000))
+    );
+    assert_eq!(
+        verify_against(encoded, &decoded[..1510], None),
+        Ok(Some(1510))
+    );
+    assert_eq!(verify_against(encoded, &[0u8; 3073], None), Ok(Some(1)));
+
+    let mut longer = [0u8; 3073];
+    longer[..3072].copy_from_slice(&decoded);
+    assert_eq!(verify_against(encoded, &longer, None), Ok(Some(3072)));
+}
+
+#[test]
+fn verify_against_errors() {
+    assert_eq!(
+        verify_against(&[0b00111000], &[0x00], None),
+        Err(DecodeSliceError::TruncatedInput)
+    );
+
+    let mut encoded = [0u8; 64];
+    let encoded_len = encode_bits_into_slice(&[0x12, 0x30], 12, &mut encoded).unwrap();
+
+    assert_eq!(
+        verify_against(&encoded[..encoded_len], &[0x12], None),
+        Err(DecodeSliceError::Unaligned)
+    );
+}
+
+#[test]
+#[should_panic]
+fn verify_zero_buffer() {
+    let mut verifier = Verifier::new(&[0x00]);
+    let _ = verifier.verify::<0>(&[0x00], None);
+}
+
+#[test]
+fn delta_round_trip() {
+    let mut reference = [0u8; 4096];
+
+    for (index, byte) in reference.iter_mut().enumerate() {
+        *byte = (index * index / 7) as u8 ^ (index >> 4) as u8;
+    }
+
+    let mut target = [0u8; 4100];
+    target[..4096].copy_from_slice(&reference);
+    target[17] ^= 0x04;
+    target[2000..2003].fill(0xA5);
+    target[4099] = 0x80;
+
+    let mut patch = [0u8; 64];
+    let patch_len = encode_delta(&reference, &target, &mut patch).unwrap();
+    let patch = &patch[..patch_len];
+
+    assert!(patch_len < 32);
+
+    let mut decoded = [0u8; 4100];
+    assert_eq!(decode_delta(&reference[..], patch, &mut decoded), Ok(4100));
+    assert_eq!(decoded, target);
+
+    let mut decoder = DeltaDecoder::new(&reference[..]);
+    let mut decoded_len = 0;
+
+    for byte in patch {
+        loop {
+            let produced = &mut decoded[decoded_len..][..(4100 - decoded_len).min(100)];
+            let (consumed_len, produced_len, state) =
+                decoder.step(core::slice::from_ref(byte), produced).unwrap();
+            decoded_len += produced_len;
+
+            if consumed_len != 0 || state != DecoderState::CanProduce {
+                break;
+            }
+        }
+    }
+
+    assert_eq!(decoded_len, 4100);
+    assert_eq!(decoded, target);
+
+    assert_eq!(
+        encode_delta(&reference, &reference[..1000], &mut [0u8; 64]),
+        encode_into_slice(&[0x00; 1000], &mut [0u8; 64])
+    );
+}
+
+#[test]
+fn delta_errors() {
+    let reference = [0x5Au8; 256];
+
+    assert_eq!(
+        encode_delta(&reference, &[0x0F; 256], &mut [0u8; 16]),
+        Err(EncodeSliceError::NeedsMoreSpace)
+    );
+
+    let mut patch = [0u8; 16];
+    let patch_len = encode_delta(&reference, &[0x5B; 8], &mut patch).unwrap();
+
+    assert_eq!(
+        decode_delta(&reference[..], &patch[..patch_len - 1], &mut [0u8; 8]),
+        Err(DeltaDecodeError::Decode(DecodeSliceError::TruncatedInput))
+    );
+    assert_eq!(
+        decode_delta(&reference[..], &patch[..patch_len], &mut [0u8; 4]),
+        Err(DeltaDecodeError::Decode(DecodeSliceError::NeedsMoreSpace))
+    );
+}
+
+#[test]
+fn frame_round_trip() {
+    let mut decoded = [0u8; 3000];
+
+    for (row, chunk) in decoded.chunks_exact_mut(60).enumerate() {
+        chunk[..20].fill(0x96);
+        chunk[20..24].copy_from_slice(&[0x12, 0x34, 0x56, 0x78]);
+        chunk[45] = 0xC3;
+        chunk[59] = if row % 10 == 0 { 0x01 } else { 0x00 };
+    }
+
+    let mut encoded = [0u8; 4096];
+    let encoded_len = encode_frames_into_slice::<60>(&decoded, &mut encoded).unwrap();
+    let plain_len = encode_into_slice(&decoded, &mut [0u8; 4096]).unwrap();
+
+    assert!(encoded_len * 8 < plain_len);
+
+    let mut recoded = [0u8; 3000];
+    assert_eq!(
+        decode_frames_from_slice::<60>(&encoded[..encoded_len], &mut recoded),
+        Ok(3000)
+    );
+    assert_eq!(recoded, decoded);
+
+    let mut encoder = FrameEncoder::<60>::new(Encoder::new());
+    let mut streamed = [0u8; 4096];
+    let mut streamed_len = 0;
+
+    for chunk in decoded.chunks(77) {
+        let mut chunk_len = 0;
+
+        while chunk_len < chunk.len() {
+            let produced = &mut streamed[streamed_len..][..3];
+            let (consumed_len, produced_len, _) = encoder.step(&chunk[chunk_len..], produced);
+            chunk_len += consumed_len;
+            streamed_len += produced_len;
+        }
+    }
+
+    encoder.set_consumed_bytes_end();
+
+    loop {
+        let produced = &mut streamed[streamed_len..][..3];
+        let (_, produced_len, state) = encoder.step(&[], produced);
+        streamed_len += produced_len;
+
+        if state == EncoderState::Terminated {
+            break;
+        }
+    }
+
+    assert_eq!(&streamed[..streamed_len], &encoded[..encoded_len]);
+
+    let mut decoder = FrameDecoder::<60>::new(Decoder::new());
+    let mut recoded_len = 0;
+
+    for byte in &encoded[..encoded_len] {
+        loop {
+            let produced = &mut recoded[recoded_len..][..(3000 - recoded_len).min(7)];
+            let (consumed_len, produced_len, state) =
+                decoder.step(core::slice::from_ref(byte), produced);
+            recoded_len += produced_len;
+
+            if consumed_len != 0 || state != DecoderState::CanProduce {
+                break;
+            }
+        }
+    }
+
+    assert_eq!(recoded_len, 3000);
+    assert_eq!(recoded, decoded);
+}
+
+#[test]
+fn frame_format_v2() {
+    let mut decoded = [0u8; 1024];
+
+    for chunk in decoded.chunks_exact_mut(16) {
+        chunk.copy_from_slice(b"zerocrush frames");
+    }
+
+    let mut encoder = FrameEncoder::<16>::new(Encoder::with_format(Format::V2));
+    encoder.set_consumed_bytes_end();
+
+    let mut encoded = [0u8; 64];
+    let (_, encoded_len, state) = encoder.step(&decoded, &mut encoded);
+    assert_eq!(state, EncoderState::Terminated);
+
+    let mut decoder = FrameDecoder::<16>::with_extended(ExtendedDecoder::new());
+    let mut recoded = [0u8; 1024];
+
+    let (_, recoded_len, state) = decoder.step(&encoded[..encoded_len], &mut recoded);
+
+    assert_eq!(
+        state,
+        DecoderState::Terminated {
+            corrupted: false,
+            unaligned: false,
+        }
+    );
+    assert_eq!(recoded_len, 1024);
+    assert_eq!(recoded, decoded);
+}
+
+#[test]
+fn positions_round_trip() {
+    let mut decoded = [0u8; 3072];
+    decoded[1] = 0x0F;
+    decoded[2] = 0xFF;
+    decoded[1500..1520].fill(0xFF);
+    decoded[3071] = 0x81;
+
+    let mut encoded = [0u8; 64];
+    let encoded_len = encode_into_slice(&decoded, &mut encoded).unwrap();
+    let encoded = &encoded[..encoded_len];
+
+    let positions = (0..decoded.len() as u64 * 8)
+        .filter(|&bit_pos| decoded[bit_pos as usize / 8] & (0x80 >> (bit_pos % 8)) != 0);
+
+    assert!(ones(encoded).map(Result::unwrap).eq(positions.clone()));
+
+    let mut recoded = [0u8; 64];
+    let recoded_len = encode_positions(positions, 24576, &mut recoded).unwrap();
+
+    assert_eq!(&recoded[..recoded_len], encoded);
+}
+
+#[test]
+fn positions_edge_cases() {
+    let mut encoded = [0u8; 64];
+
+    let encoded_len = encode_positions([], 0, &mut encoded).unwrap();
+    assert_eq!(ones(&encoded[..encoded_len]).next(), None);
+
+    let encoded_len = encode_positions([0, 1, 2, 9], 10, &mut encoded).unwrap();
+    let mut expected = [0u8; 64];
+    let expected_len = encode_bits_into_slice(&[0xE0, 0x40], 10, &mut expected).unwrap();
+    assert_eq!(&encoded[..encoded_len], &expected[..expected_len]);
+
+    let mut iter = ones(&[0b00111000]);
+    assert_eq!(iter.next(), Some(Err(DecodeSliceError::TruncatedInput)));
+    assert_eq!(iter.next(), None);
+
+    assert_eq!(
+        encode_positions([3], 4, &mut [0u8; 2]),
+        Err(EncodeSliceError::NeedsMoreSpace)
+    );
+}
+
+#[test]
+#[should_panic]
+fn positions_unsorted() {
+    let _ = encode_positions([3, 3], 4, &mut [0u8; 64]);
+}
+
+#[test]
+#[should_panic]
+fn encode_bits_short_input() {
+    let _ = encode_bits_into_slice(&[], 3, &mut [0u8; 64]);
+}
+
+#[cfg(feature = "alloc")]
+mod bitmap {
+    use crate::*;
+    use alloc::vec::Vec;
+
+    fn assert_canonical(bitmap: &CompressedBitmap, decoded: &[u8]) {
+        let mut encoded = [0u8; 256];
+        let encoded_len = encode_bits_into_slice(decoded, bitmap.len(), &mut encoded).unwrap();
+
+        assert_eq!(bitmap.as_encoded(), &encoded[..encoded_len]);
+
+        for index in (0..bitmap.len()).step_by(61) {
+            let bit = decoded[index as usize / 8] & (0x80 >> (index % 8)) != 0;
+            assert_eq!(bitmap.get(index), bit);
+        }
+    }
+
+    #[test]
+    fn bitmap_set_clear() {
+        let mut decoded = [0u8; 2048];
+        let mut bitmap = CompressedBitmap::new(16384);
+
+        assert_canonical(&bitmap, &decoded);
+
+        let mut state = 0x2545F491u32;
+
+        for _ in 0..200 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+
+            let index = match state % 4 {
+                0 => state / 4 % 16384,
+                1 => state / 4 % 64,
+                2 => 16383 - state / 4 % 64,
+                _ => 8000 + state / 4 % 16,
+            } as u64;
+
+            if state & 0x80000000 != 0 {
+                bitmap.set(index);
+                decoded[index as usize / 8] |= 0x80 >> (index % 8);
+            } else {
+                bitmap.clear(index);
+                decoded[index as usize / 8] &= !(0x80 >> (index % 8));
+            }
+
+            assert_canonical(&bitmap, &decoded);
+        }
+
+        let expected: Vec<u64> = (0..16384)
+            .filter(|&index| decoded[index as usize / 8] & (0x80 >> (index % 8)) != 0)
+            .collect();
+
+        assert_eq!(bitmap.ones().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn bitmap_long_runs() {
+        let mut decoded = [0u8; 4096];
+        decoded[..2048].fill(0xFF);
+
+        let mut encoded = [0u8; 64];
+        let encoded_len = encode_into_slice(&decoded, &mut encoded).unwrap();
+
+        let mut bitmap = CompressedBitmap::from_encoded(encoded[..encoded_len].to_vec()).unwrap();
+        assert_eq!(bitmap.len(), 32768);
+
+        for index in [0, 16383, 16384, 32767, 9000, 25000] {
+            bitmap.clear(index);
+            decoded[index as usize / 8] &= !(0x80 >> (index % 8));
+            assert_canonical(&bitmap, &decoded);
+
+            bitmap.set(index);
+            decoded[index as usize / 8] |= 0x80 >> (index % 8);
+            assert_canonical(&bitmap, &decoded);
+        }
+    }
+
+    #[test]
+    fn bitmap_unaligned() {
+        let mut bitmap = CompressedBitmap::new(13);
+
+        bitmap.set(0);
+        bitmap.set(12);
+        bitmap.set(11);
+        bitmap.clear(0);
+
+        assert_canonical(&bitmap, &[0x00, 0b00011000]);
+        assert_eq!(bitmap.ones().collect::<Vec<_>>(), [11, 12]);
+    }
+
+    #[test]
+    fn bitmap_from_encoded() {
+        let mut encoded = [0u8; 64];
+        let encoded_len = encode_into_slice(&[0x12, 0x34], &mut encoded).unwrap();
+
+        let mut data = encoded[..encoded_len].to_vec();
+        data.extend_from_slice(&[0xAA, 0xBB]);
+
+        let bitmap = CompressedBitmap::from_encoded(data).unwrap();
+
+        assert_eq!(bitmap.len(), 16);
+        assert_eq!(bitmap.into_encoded(), &encoded[..encoded_len]);
+
+        assert_eq!(
+            CompressedBitmap::from_encoded([0b00111000].to_vec()).err(),
+            Some(DecodeSliceError::TruncatedInput)
+        );
+
+        assert!(CompressedBitmap::new(0).is_empty());
+    }
+}
+
+#[cfg(feature = "alloc")]
+mod train {
+    use crate::*;
+
+    fn assert_valid(payload_bits: &[[u8; 12]; 2]) {
+        for table in payload_bits {
+            for (index, &bits) in table.iter().enumerate() {
+                assert!(index + 1 + bits as usize <= 24);
+            }
+        }
+    }
+
+    #[test]
+    fn train_sparse() {
+        let mut sample = [0u8; 4096];
+
+        for index in (0..4096).step_by(37) {
+            sample[index] = 0x18;
+        }
+
+        let mut trainer = CodeTrainer::new();
+        trainer.add_sample(&sample);
+
+        let payload_bits = trainer.train();
+        assert_valid(&payload_bits);
+
+        assert_eq!(
+            payload_bits,
+            [[2, 8, 0, 5, 0, 0, 0, 0, 0, 0, 0, 0], [0; 12]]
+        );
+
+        assert_eq!(
+            trainer.estimate_bits(&payload_bits),
+            3 + 10 + 110 * 5 + 111 * 2
+        );
+        assert_eq!(trainer.estimate_default_bits(), 4 + 14 + 110 * 16 + 111 * 2);
+    }
+
+    #[test]
+    fn train_long_ones() {
+        let mut trainer = CodeTrainer::new();
+
+        for len in [100, 200, 300, 400] {
+            let mut sample = [0u8; 1024];
+            sample[len..2 * len].fill(0xFF);
+            trainer.add_sample(&sample);
+        }
+
+        let payload_bits = trainer.train();
+        assert_valid(&payload_bits);
+
+        assert!(payload_bits[1].iter().any(|&bits| bits != 0));
+        assert!(trainer.estimate_bits(&payload_bits) < trainer.estimate_default_bits());
+    }
+
+    fn runs_sample(runs: &[u64]) -> alloc::vec::Vec<u8> {
+        let mut sample = alloc::vec::Vec::new();
+        let mut bit_len = 0;
+
+        for (index, &len) in runs.iter().enumerate() {
+            for _ in 0..len {
+                if bit_len % 8 == 0 {
+                    sample.push(0);
+                }
+
+                if index % 2 == 1 {
+                    *sample.last_mut().unwrap() |= 0x80 >> (bit_len % 8);
+                }
+
+                bit_len += 1;
+            }
+        }
+
+        sample
+    }
+
+    fn estimate_matches_encoded<C: Code>() {
+        let chunks = C::PAYLOAD_BITS
+            .map(|table| table.iter().map(|&bits| 1u64 << bits).sum::<u64>() + 1 + 4093);
+
+        let mut trainer = CodeTrainer::new();
+        let mut encoded_bits = 0;
+        let mut sample_count = 0;
+
+        for k in 1..=2 {
+            for offset in [0, 1, 2, 3, 100, 4000, chunks[0] - 1, chunks[1] - 1] {
+                for runs in [
+                    [k * chunks[0] + offset, 3, 5],
+                    [7, k * chunks[1] + offset, 5],
+                ] {
+                    let sample = runs_sample(&runs);
+
+                    let mut encoder = Encoder::<C>::with_code(Format::V1);
+                    encoder.set_consumed_bytes_end();
+
+                    let mut encoded = [0u8; 256];
+                    let (_, encoded_len, state) = encoder.step(&sample, &mut encoded);
+                    assert_eq!(state, EncoderState::Terminated);
+
+                    let mut sample_trainer = CodeTrainer::new();
+                    sample_trainer.add_sample(&sample);
+
+                    // The termination symbol and the padding are not estimated.
+                    let estimate = sample_trainer.estimate_bits(&C::PAYLOAD_BITS);
+                    let padding = encoded_len as u64 * 8 - 24 - estimate;
+                    assert!(padding < 8, "{runs:?}");
+
+                    trainer.add_sample(&sample);
+                    encoded_bits += encoded_len as u64 * 8 - 24;
+                    sample_count += 1;
+                }
+            }
+        }
+
+        let estimate = trainer.estimate_bits(&C::PAYLOAD_BITS);
+        assert!(estimate <= encoded_bits && encoded_bits < estimate + 8 * sample_count);
+    }
+
+    #[test]
+    fn estimate_default_code() {
+        estimate_matches_encoded::<DefaultCode>();
+    }
+
+    #[test]
+    fn estimate_custom_code() {
+        estimate_matches_encoded::<super::SymmetricCode>();
+    }
+
+    #[test]
+    fn train_empty() {
+        let trainer = CodeTrainer::new();
+
+        assert_valid(&trainer.train());
+        assert_eq!(trainer.estimate_default_bits(), 0);
+    }
+}
+
+#[cfg(feature = "embedded-storage")]
+mod flash {
+    use crate::*;
+    use embedded_storage::nor_flash::{ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash};
+
+    struct MockFlash {
+        memory: [u8; 1024],
+        written: [bool; 1024],
+        erase_count: usize,
+        write_count: usize,
+    }
+
+    impl MockFlash {
+        fn new() -> Self {
+            Self {
+                memory: [0x5A; 1024],
+                written: [false; 1024],
+                erase_count: 0,
+                write_count: 0,
+            }
+        }
+    }
+
+    impl ErrorType for MockFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for MockFlash {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            bytes.copy_from_slice(&self.memory[offset as usize..][..bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.memory.len()
+        }
+    }
+
+    impl NorFlash for MockFlash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = 128;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            embedded_storage::nor_flash::check_erase(self, from, to)?;
+
+            self.memory[from as usize..to as usize].fill(0xFF);
+            self.written[from as usize..to as usize].fill(false);
+            self.erase_count += 1;
+
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            embedded_storage::nor_flash::check_write(self, offset, bytes.len())?;
+
+            let range = offset as usize..offset as usize + bytes.len();
+
+            assert!(self.written[range.clone()].iter().all(|&written| !written));
+            assert!(self.memory[range.clone()].iter().all(|&byte| byte == 0xFF));
+
+            self.memory[range.clone()].copy_from_slice(bytes);
+            self.written[range].fill(true);
+            self.write_count += 1;
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn decode_into_flash_skips_erased_runs() {
+        let mut image = [0xFFu8; 300];
+        image[0..3].copy_from_slice(&[0x12, 0x34, 0x56]);
+        image[150] = 0x00;
+        image[299] = 0x7F;
+
+        let mut encoded = [0u8; 64];
+        let encoded_len = encode_into_slice(&image, &mut encoded).unwrap();
+
+        let mut flash = MockFlash::new();
+
+        let image_len = decode_into_flash::<_, 32>(&encoded[..encoded_len], &mut flash, 128);
+
+        assert_eq!(image_len, Ok(300));
+        assert_eq!(&flash.memory[128..428], &image);
+        assert_eq!(&flash.memory[428..512], &[0xFF; 84]);
+        assert_eq!(&flash.memory[..128], &[0x5A; 128]);
+        assert_eq!(&flash.memory[512..], &[0x5A; 512]);
+        assert_eq!(flash.erase_count, 3);
+        assert_eq!(flash.write_count, 3);
+    }
+
+    #[test]
+    fn decode_into_flash_streaming_unaligned() {
+        let mut flash = MockFlash::new();
+
+        let mut decoder = FlashDecoder::<_, 8>::new(&mut flash, 0).unwrap();
+
+        let encoded = [0b00011101, 0b10000000, 0b00000111, 0b11111111, 0b10000000];
+
+        for byte in &encoded[..4] {
+            assert_eq!(decoder.write(core::slice::from_ref(byte)), Ok(None));
+        }
+
+        assert_eq!(decoder.write(&encoded[4..]), Ok(Some(4)));
+        assert_eq!(decoder.write(&[]), Ok(Some(4)));
+
+        assert_eq!(
+            &flash.memory[..8],
+            &[0x00, 0x00, 0x00, 0b00001111, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+        assert_eq!(flash.write_count, 1);
+    }
+
+    #[test]
+    fn decode_delta_from_flash() {
+        let mut flash = MockFlash::new();
+        flash.memory[256..512].fill(0x3C);
+
+        let mut target = [0x3Cu8; 300];
+        target[100] = 0x3D;
+        target[256..].fill(0x00);
+
+        let mut patch = [0u8; 64];
+        let patch_len = encode_delta(&flash.memory[256..512], &target, &mut patch).unwrap();
+
+        let reference = FlashReference::new(&mut flash, 256, 256);
+        let mut decoded = [0u8; 300];
+
+        assert_eq!(
+            decode_delta(reference, &patch[..patch_len], &mut decoded),
+            Ok(300)
+        );
+        assert_eq!(decoded, target);
+    }
+
+    #[test]
+    fn decode_into_flash_errors() {
+        let mut flash = MockFlash::new();
+
+        assert_eq!(
+            FlashDecoder::<_, 8>::new(&mut flash, 64).err(),
+            Some(FlashDecodeError::Misaligned)
+        );
+
+        assert_eq!(
+            decode_into_flash::<_, 8>(&[0b00111000], &mut flash, 0),
+            Err(FlashDecodeError::Decode(DecodeSliceError::TruncatedInput))
+        );
+
+        let mut encoded = [0u8; 16];
+        let encoded_len = encode_into_slice(&[0x00; 200], &mut encoded).unwrap();
+
+        assert_eq!(
+            decode_into_flash::<_, 8>(&encoded[..encoded_len], &mut flash, 896),
+            Err(FlashDecodeError::Decode(DecodeSliceError::NeedsMoreSpace))
+        );
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+mod ice40 {
+    use crate::*;
+    use core::cell::RefCell;
+    use core::convert::Infallible;
+    use embedded_hal::delay::DelayNs;
+    use embedded_hal::digital::{ErrorType, InputPin, OutputPin};
+    use embedded_hal::spi::{self, SpiBus};
+
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    enum Event {
+        Cs(bool),
+        Creset(bool),
+        Clocks(usize),
+        Bitstream,
+    }
+
+    struct Log {
+        events: [Option<Event>; 32],
+        events_len: usize,
+        bitstream: [u8; 64],
+        bitstream_len: usize,
+        cs: bool,
+    }
+
+    impl Log {
+        fn new() -> Self {
+            Self {
+                events: [None; 32],
+                events_len: 0,
+                bitstream: [0; 64],
+                bitstream_len: 0,
+                cs: false,
+            }
+        }
+
+        fn push(&mut self, event: Event) {
+            let last = self.events_len.checked_sub(1).and_then(|i| self.events[i]);
+
+            match (last, event) {
+                (Some(Event::Clocks(m)), Event::Clocks(n)) => {
+                    self.events[self.events_len - 1] = Some(Event::Clocks(m + n));
+                }
+                (Some(Event::Bitstream), Event::Bitstream) => {}
+                _ => {
+                    self.events[self.events_len] = Some(event);
+                    self.events_len += 1;
+                }
+            }
+        }
+
+        fn events(&self) -> &[Option<Event>] {
+            &self.events[..self.events_len]
+        }
+    }
+
+    struct MockSpi<'a>(&'a RefCell<Log>);
+    struct MockCs<'a>(&'a RefCell<Log>);
+    struct MockCreset<'a>(&'a RefCell<Log>);
+    struct MockCdone(bool);
+    struct MockDelay;
+
+    impl spi::ErrorType for MockSpi<'_> {
+        type Error = spi::ErrorKind;
+    }
+
+    impl SpiBus for MockSpi<'_> {
+        fn read(&mut self, _: &mut [u8]) -> Result<(), Self::Error> {
+            unimplemented!()
+        }
+
+        fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+            let mut log = self.0.borrow_mut();
+
+            if log.cs {
+                log.push(Event::Clocks(words.len() * 8));
+            } else {
+                let offset = log.bitstream_len;
+                log.bitstream[offset..][..words.len()].copy_from_slice(words);
+                log.bitstream_len += words.len();
+                log.push(Event::Bitstream);
+            }
+
+            Ok(())
+        }
+
+        fn transfer(&mut self, _: &mut [u8], _: &[u8]) -> Result<(), Self::Error> {
+            unimplemented!()
+        }
+
+        fn transfer_in_place(&mut self, _: &mut [u8]) -> Result<(), Self::Error> {
+            unimplemented!()
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl ErrorType for MockCs<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for MockCs<'_> {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.0.borrow_mut().cs = false;
+            self.0.borrow_mut().push(Event::Cs(false));
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.0.borrow_mut().cs = true;
+            self.0.borrow_mut().push(Event::Cs(true));
+            Ok(())
+        }
+    }
+
+    impl ErrorType for MockCreset<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for MockCreset<'_> {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.0.borrow_mut().push(Event::Creset(false));
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.0.borrow_mut().push(Event::Creset(true));
+            Ok(())
+        }
+    }
+
+    impl ErrorType for MockCdone {
+        type Error = Infallible;
+    }
+
+    impl InputPin for MockCdone {
+        fn is_high(&mut self) -> Result<bool, Self::Error> {
+            Ok(self.0)
+        }
+
+        fn is_low(&mut self) -> Result<bool, Self::Error> {
+            Ok(!self.0)
+        }
+    }
+
+    impl DelayNs for MockDelay {
+        fn delay_ns(&mut self, _: u32) {}
+    }
+
+    #[test]
+    fn ice40_configure() {
+        let log = RefCell::new(Log::new());
+
+        let mut bitstream = [0u8; 40];
+        bitstream[..4].copy_from_slice(&[0x7E, 0xAA, 0x99, 0x7E]);
+        bitstream[36..].copy_from_slice(&[0x01, 0x06, 0x00, 0x00]);
+
+        let mut encoded = [0u8; 64];
+        let encoded_len = encode_into_slice(&bitstream, &mut encoded).unwrap();
+
+        let mut ice40 = Ice40::new(
+            MockSpi(&log),
+            MockCs(&log),
+            MockCreset(&log),
+            MockCdone(true),
+            MockDelay,
+        );
+
+        assert_eq!(ice40.configure::<16>(&encoded[..encoded_len]), Ok(()));
+
+        let log = log.borrow();
+
+        assert_eq!(&log.bitstream[..log.bitstream_len], &bitstream);
+        assert_eq!(
+            log.events(),
+            &[
+                Some(Event::Cs(false)),
+                Some(Event::Creset(false)),
+                Some(Event::Creset(true)),
+                Some(Event::Cs(true)),
+                Some(Event::Clocks(8)),
+                Some(Event::Cs(false)),
+                Some(Event::Bitstream),
+                Some(Event::Cs(true)),
+                Some(Event::Clocks(160)),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn ice40_write_zero_buffer() {
+        let log = RefCell::new(Log::new());
+
+        let mut ice40 = Ice40::new(
+            MockSpi(&log),
+            MockCs(&log),
+            MockCreset(&log),
+            MockCdone(true),
+            MockDelay,
+        );
+
+        let _ = ice40.write::<0>(&[0x00]);
+    }
+    #[test]
+    fn ice40_not_done() {
+        let log = RefCell::new(Log::new());
+
+        let mut encoded = [0u8; 16];
+        let encoded_len = encode_into_slice(&[0x7E, 0xAA, 0x99, 0x7E], &mut encoded).unwrap();
+
+        let mut ice40 = Ice40::new(
+            MockSpi(&log),
+            MockCs(&log),
+            MockCreset(&log),
+            MockCdone(false),
+            MockDelay,
+        );
+
+        assert_eq!(
+            ice40.configure::<16>(&encoded[..encoded_len]),
+            Err(Ice40Error::NotDone)
+        );
+
+        assert_eq!(
+            ice40.configure::<16>(&encoded[..encoded_len - 1]),
+            Err(Ice40Error::Decode(DecodeSliceError::TruncatedInput))
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use crate::{Code, DefaultCode};
+use alloc::collections::BTreeMap;
+use alloc::vec;
+
+/// Run length up to which the trainer optimizes the shortest symbols of each mode exactly; longer
+/// runs are assumed to be represented by the longest symbols.
+const MAX_BASE: usize = 1 << 14;
+
+/// Helper to derive code tables (see [`Code`]) from run length statistics gathered over a corpus
+/// of sample inputs.
+///
+/// Sizes are estimated under the original format, in which runs too long for the longest symbols
+/// begin with continuated symbols. While training, the remainder of such a run is assumed to cost
+/// one longest symbol, as the cost of the shorter symbols is not yet known.
+#[derive(Clone, Debug, Default)]
+pub struct CodeTrainer {
+    runs: [BTreeMap<u64, u64>; 2],
+}
+
+impl CodeTrainer {
+    /// Constructs a new trainer without any statistics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gathers the run lengths of the given sample input.
+    pub fn add_sample(&mut self, input: &[u8]) {
+        let mut bit = false;
+        let mut len = 0;
+
+        for &byte in input {
+            let mut byte = byte;
+            let mut remaining = 8;
+
+            while remaining != 0 {
+                let count = if bit {
+                    byte.leading_ones()
+                } else {
+                    byte.leading_zeros()
+                }
+                .min(remaining);
+
+                len += count as u64;
+                remaining -= count;
+                byte = byte.checked_shl(count).unwrap_or(0);
+
+                if remaining != 0 {
+                    self.add_run(bit, len);
+                    bit = !bit;
+                    len = 0;
+                }
+            }
+        }
+
+        self.add_run(bit, len);
+    }
+
+    /// Derives the payload lengths of the code minimizing the estimated size of the samples.
+    pub fn train(&self) -> [[u8; 12]; 2] {
+        [self.train_mode(0), self.train_mode(1)]
+    }
+
+    /// Estimates the size in bits of the symbols representing the samples using the given payload
+    /// lengths, excluding the termination symbol and any leading mode change symbol.
+    pub fn estimate_bits(&self, payload_bits: &[[u8; 12]; 2]) -> u64 {
+        let mut total = 0;
+
+        for (mode, runs) in self.runs.iter().enumerate() {
+            let bases = bases(&payload_bits[mode]);
+
+            for (&len, &count) in runs {
+                let chunks = len / (bases[12] + 4093);
+                let remainder = len % (bases[12] + 4093);
+
+                // A run ending exactly with a continuated symbol is followed by a mode change.
+                let cost = match bases.iter().rposition(|&base| base <= remainder) {
+                    Some(12) | None => 24,
+                    Some(index) => (index + 1 + payload_bits[mode][index] as usize) as u64,
+                };
+
+                total += (24 * chunks + cost) * count;
+            }
+        }
+
+        total
+    }
+
+    /// Estimates the size in bits of the symbols representing the samples using the default code,
+    /// excluding special symbols.
+    pub fn estimate_default_bits(&self) -> u64 {
+        self.estimate_bits(&DefaultCode::PAYLOAD_BITS)
+    }
+
+    fn add_run(&mut self, bit: bool, len: u64) {
+        if len != 0 {
+            *self.runs[bit as usize].entry(len).or_default() += 1;
+        }
+    }
+
+    fn train_mode(&self, mode: usize) -> [u8; 12] {
+        let runs = &self.runs[mode];
+
+        // Number of runs shorter than each length, and the cost of the runs at least that long
+        // (with room for the difference past the longest length).
+        let mut shorter = vec![0u64; MAX_BASE + 2];
+        let mut tail = vec![0u64; MAX_BASE + 3];
+
+        for (&len, &count) in runs.range(..=MAX_BASE as u64) {
+            shorter[len as usize + 1] += count;
+        }
+
+        for len in 1..shorter.len() {
+            shorter[len] += shorter[len - 1];
+        }
+
+        // Each run costs one longest symbol for every base up to its length, plus its continuated
+        // symbols, whose number is constant over a few ranges of bases. These costs are recorded as
+        // differences between consecutive bases and then summed in a single pass.
+        for (&len, &count) in runs {
+            let end = len.min(MAX_BASE as u64 + 1);
+            let mut base = 1;
+
+            tail[1] = tail[1].wrapping_add(24 * count);
+            tail[end as usize + 1] = tail[end as usize + 1].wrapping_sub(24 * count);
+
+            while base <= end {
+                let chunks = len / (base + 4093);
+
+                if chunks == 0 {
+                    break;
+                }
+
+                let last = (len / chunks - 4093).min(end);
+
+                tail[base as usize] = tail[base as usize].wrapping_add(24 * chunks * count);
+                tail[last as usize + 1] = tail[last as usize + 1].wrapping_sub(24 * chunks * count);
+
+                base = last + 1;
+            }
+        }
+
+        for base in 1..tail.len() {
+            tail[base] = tail[base].wrapping_add(tail[base - 1]);
+        }
+
+        // Minimum cost of the runs shorter than each base reachable after each symbol, saturating
+        // bases at the maximum, along with the payload length achieving it.
+        let mut costs = vec![vec![u64::MAX; MAX_BASE + 2]; 13];
+        let mut choices = vec![vec![0u8; MAX_BASE + 2]; 13];
+
+        costs[0][1] = 0;
+
+        for index in 0..12 {
+            for base in 1..=MAX_BASE + 1 {
+                let cost = costs[index][base];
+
+                if cost == u64::MAX {
+                    continue;
+                }
+
+                for payload_bits in 0..=(23 - index) {
+                    let next = (base + (1 << payload_bits)).min(MAX_BASE + 1);
+                    let count = shorter[next] - shorter[base];
+                    let next_cost = cost + count * (index + 1 + payload_bits) as u64;
+
+                    if next_cost < costs[index + 1][next] {
+                        costs[index + 1][next] = next_cost;
+                        choices[index + 1][next] = payload_bits as u8;
+                    }
+                }
+            }
+        }
+
+        let mut base = (1..=MAX_BASE + 1)
+            .filter(|&base| costs[12][base] != u64::MAX)
+            .min_by_key(|&base| costs[12][base] + tail[base])
+            .unwrap();
+
+        let mut payload_bits = [0; 12];
+
+        for index in (0..12).rev() {
+            payload_bits[index] = choices[index + 1][base];
+
+            base = (1..=base)
+                .rev()
+                .find(|&prev| {
+                    costs[index][prev] != u64::MAX
+                        && (prev + (1 << payload_bits[index])).min(MAX_BASE + 1) == base
+                        && costs[index][prev]
+                            + (shorter[base] - shorter[prev])
+                                * (index + 1 + payload_bits[index] as usize) as u64
+                            == costs[index + 1][base]
+                })
+                .unwrap();
+        }
+
+        payload_bits
+    }
+}
+
+/// Returns the shortest run length represented by each symbol, followed by that of the longest
+/// symbols.
+fn bases(payload_bits: &[u8; 12]) -> [u64; 13] {
+    let mut bases = [1; 13];
+
+    for index in 0..12 {
+        bases[index + 1] = bases[index] + (1 << payload_bits[index]);
+    }
+
+    bases
+}
@@ -0,0 +1,187 @@
+use crate::{DecodeSliceError, Decoder, DecoderState, Extent};
+
+/// Errors that may occur while verifying data against encoded data.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VerifyError {
+    /// The data differs from the decoded data starting at the given byte offset, which is also
+    /// reported if either of them ends before the other.
+    Mismatch(u64),
+    /// The encoded data could not be decoded.
+    Decode(DecodeSliceError),
+}
+
+/// Helper to verify data supplied in chunks against encoded data held in a byte slice, without
+/// decoding the latter into memory.
+///
+/// Runs covering whole bytes are compared without being decoded, while other decoded bytes are
+/// compared in chunks of at most `N` bytes on the stack. An optional mask can be supplied along
+/// with each chunk of data, in which case bits set in the mask are not compared. The verifier must
+/// not be used any further once an error has been reported.
+#[derive(Debug)]
+pub struct Verifier<'a> {
+    decoder: Decoder,
+    input: &'a [u8],
+    offset: u64,
+    fill: Option<(u8, u64)>,
+    done: bool,
+}
+
+impl<'a> Verifier<'a> {
+    /// Constructs a new verifier against the given encoded data.
+    pub const fn new(input: &'a [u8]) -> Self {
+        Self {
+            decoder: Decoder::new(),
+            input,
+            offset: 0,
+            fill: None,
+            done: false,
+        }
+    }
+
+    /// Returns the number of bytes verified so far.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Verifies the next chunk of data (with its mask, if any) against the decoded data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is zero or if the mask is not as long as the data.
+    pub fn verify<const N: usize>(
+        &mut self,
+        mut data: &[u8],
+        mut mask: Option<&[u8]>,
+    ) -> Result<(), VerifyError> {
+        assert!(N != 0);
+        assert!(mask.is_none_or(|mask| mask.len() == data.len()));
+
+        let mut buffer = [0u8; N];
+
+        while !data.is_empty() {
+            if let Some((byte, len)) = self.fill {
+                let amount = len.min(data.len() as u64) as usize;
+
+                self.compare(&data[..amount], mask, |_| byte)?;
+                self.fill = Some((byte, len - amount as u64)).filter(|&(_, len)| len != 0);
+
+                data = &data[amount..];
+                mask = mask.map(|mask| &mask[amount..]);
+
+                continue;
+            }
+
+            if self.done {
+                return Err(VerifyError::Mismatch(self.offset));
+            }
+
+            let amount = data.len().min(N);
+
+            let (consumed_len, extent, state) =
+                self.decoder.step_extent(self.input, &mut buffer[..amount]);
+            self.input = &self.input[consumed_len..];
+
+            match extent {
+                Extent::Fill { byte, len } => self.fill = Some((byte, len)),
+                Extent::Data(decoded) => {
+                    let amount = decoded.len();
+
+                    self.compare(&data[..amount], mask, |index| decoded[index])?;
+
+                    data = &data[amount..];
+                    mask = mask.map(|mask| &mask[amount..]);
+                }
+            }
+
+            self.check_state(state)?;
+        }
+
+        Ok(())
+    }
+
+    /// Completes verification once all data has been verified, checking that the decoded data
+    /// does not extend past it and returning its length in bytes.
+    pub fn finish(mut self) -> Result<u64, VerifyError> {
+        while !self.done {
+            if self.fill.is_some() {
+                return Err(VerifyError::Mismatch(self.offset));
+            }
+
+            let (consumed_len, extent, state) = self.decoder.step_extent(self.input, &mut []);
+            self.input = &self.input[consumed_len..];
+
+            if let Extent::Fill { byte, len } = extent {
+                self.fill = Some((byte, len));
+            } else if state == DecoderState::CanProduce {
+                return Err(VerifyError::Mismatch(self.offset));
+            }
+
+            self.check_state(state)?;
+        }
+
+        if self.fill.is_some() {
+            return Err(VerifyError::Mismatch(self.offset));
+        }
+
+        Ok(self.offset)
+    }
+
+    fn compare(
+        &mut self,
+        data: &[u8],
+        mask: Option<&[u8]>,
+        expected: impl Fn(usize) -> u8,
+    ) -> Result<(), VerifyError> {
+        for (index, &byte) in data.iter().enumerate() {
+            let ignored = mask.map_or(0, |mask| mask[index]);
+
+            if (byte ^ expected(index)) & !ignored != 0 {
+                return Err(VerifyError::Mismatch(self.offset + index as u64));
+            }
+        }
+
+        self.offset += data.len() as u64;
+
+        Ok(())
+    }
+
+    fn check_state(&mut self, state: DecoderState) -> Result<(), VerifyError> {
+        match state {
+            DecoderState::CanConsume => Err(VerifyError::Decode(DecodeSliceError::TruncatedInput)),
+            DecoderState::CanProduce => Ok(()),
+            DecoderState::Terminated {
+                corrupted: true, ..
+            } => Err(VerifyError::Decode(DecodeSliceError::Corrupted)),
+            DecoderState::Terminated {
+                unaligned: true, ..
+            } => Err(VerifyError::Decode(DecodeSliceError::Unaligned)),
+            DecoderState::Terminated { .. } => {
+                self.done = true;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Convenient helper function to verify data (with an optional mask) against encoded data held in
+/// a byte slice, returning the byte offset of the first mismatch if any.
+///
+/// Bits set in the mask are not compared. A mismatch is also reported at the end of the shorter of
+/// the data and the decoded data if their lengths differ.
+pub fn verify_against(
+    input: &[u8],
+    data: &[u8],
+    mask: Option<&[u8]>,
+) -> Result<Option<u64>, DecodeSliceError> {
+    let mut verifier = Verifier::new(input);
+
+    let result = verifier
+        .verify::<64>(data, mask)
+        .and_then(|()| verifier.finish());
+
+    match result {
+        Ok(_) => Ok(None),
+        Err(VerifyError::Mismatch(offset)) => Ok(Some(offset)),
+        Err(VerifyError::Decode(err)) => Err(err),
+    }
+}